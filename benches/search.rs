@@ -0,0 +1,98 @@
+//! Compares the `SearchStrategy`s of `SortedList` and `FrozenSortedList` against
+//! `slice::binary_search`.
+//!
+//! Run with `cargo bench --bench search`.
+
+extern crate sorted_list;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use sorted_list::{
+    BinarySearch, EytzingerSearch, InterpolationSearch, LearnedIndex, SearchStrategy, SortedList,
+};
+
+const KEYS: u64 = 1_000_000;
+const LOOKUPS: usize = 2_000_000;
+
+/// Keys to look up, half of which are in the list
+fn lookups(max: u64) -> Vec<u64> {
+    // xorshift, so that the runs are comparable
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    (0..LOOKUPS)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % max
+        })
+        .collect()
+}
+
+fn report(name: &str, began: Instant, found: usize) {
+    let nanos = began.elapsed().as_nanos() as f64 / LOOKUPS as f64;
+    println!("  {:<24} {:>8.1} ns/lookup ({} found)", name, nanos, found);
+}
+
+fn bench_slice(keys: &[u64], lookups: &[u64]) {
+    let began = Instant::now();
+    let found = lookups
+        .iter()
+        .filter(|key| black_box(keys).binary_search(key).is_ok())
+        .count();
+    report("slice::binary_search", began, found);
+}
+
+fn bench_list<S: SearchStrategy<u64> + 'static>(
+    name: &str,
+    search: S,
+    keys: &[u64],
+    lookups: &[u64],
+) {
+    let mut list = SortedList::new().with_search(search);
+    for &key in keys {
+        list.insert(key, ());
+    }
+    // searching the layouts and models built for the final keys, not the stale ones
+    list.rebuild_search();
+
+    let began = Instant::now();
+    let found = lookups
+        .iter()
+        .filter(|key| black_box(&list).contains_key(key))
+        .count();
+    report(name, began, found);
+}
+
+fn bench_frozen(keys: &[u64], lookups: &[u64]) {
+    let list = keys
+        .iter()
+        .map(|&key| (key, ()))
+        .collect::<SortedList<_, _>>();
+    let frozen = list.freeze();
+
+    let began = Instant::now();
+    let found = lookups
+        .iter()
+        .filter(|key| black_box(&frozen).contains_key(key))
+        .count();
+    report("FrozenSortedList", began, found);
+}
+
+fn bench_all(title: &str, keys: Vec<u64>) {
+    let max = keys.last().unwrap() + 1;
+    let lookups = lookups(max);
+
+    println!("{} ({} keys)", title, keys.len());
+    bench_slice(&keys, &lookups);
+    bench_list("BinarySearch", BinarySearch, &keys, &lookups);
+    bench_list("InterpolationSearch", InterpolationSearch, &keys, &lookups);
+    bench_list("EytzingerSearch", EytzingerSearch::new(), &keys, &lookups);
+    bench_list("LearnedIndex", LearnedIndex::new(), &keys, &lookups);
+    bench_frozen(&keys, &lookups);
+}
+
+fn main() {
+    bench_all("evenly spread", (0..KEYS).map(|i| i * 2).collect());
+    bench_all("skewed", (0..KEYS).map(|i| i * i / 1000).collect());
+}
@@ -0,0 +1,262 @@
+//! Pairs kept sorted from both sides.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Bound, RangeBounds};
+
+use super::{SortedList, Tuples};
+
+/// Many-to-many map of `(A, B)` pairs ordered both by `A` and by `B`, made of two `SortedList`s
+/// which are always changed together.
+///
+/// Every pair is stored once on both sides. The values of each key are in insertion order on
+/// both sides.
+///
+/// # Example
+///
+/// Edges of a graph, with the reverse edges available too:
+///
+/// ```
+/// use sorted_list::BiSortedList;
+///
+/// let mut edges = BiSortedList::new();
+/// edges.insert('a', 'b');
+/// edges.insert('a', 'c');
+/// edges.insert('b', 'c');
+///
+/// assert_eq!(edges.values_of_left(&'a'), &['b', 'c']);
+/// assert_eq!(edges.values_of_right(&'c'), &['a', 'b']);
+///
+/// edges.remove(&'a', &'c');
+/// assert_eq!(edges.values_of_right(&'c'), &['b']);
+/// ```
+pub struct BiSortedList<A: Ord, B: Ord> {
+    left: SortedList<A, B>,
+    right: SortedList<B, A>,
+}
+
+impl<A: Ord + Clone, B: Ord + Clone> BiSortedList<A, B> {
+    /// Creates a new empty list
+    pub fn new() -> Self {
+        BiSortedList {
+            left: SortedList::new(),
+            right: SortedList::new(),
+        }
+    }
+
+    /// Returns the number of pairs
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    /// Returns `true` if there are no pairs
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Inserts the pair on both sides, returning `false` if it already exists
+    pub fn insert(&mut self, a: A, b: B) -> bool {
+        if !self.left.insert(a.clone(), b.clone()) {
+            return false;
+        }
+
+        let inserted = self.right.insert(b, a);
+        debug_assert!(inserted, "sides out of sync");
+        true
+    }
+
+    /// Removes the pair from both sides, returning `true` if it existed
+    pub fn remove(&mut self, a: &A, b: &B) -> bool {
+        if !self.left.remove(a, b) {
+            return false;
+        }
+
+        let removed = self.right.remove(b, a);
+        debug_assert!(removed, "sides out of sync");
+        true
+    }
+
+    /// Removes every pair with `a` on the left, returning the right sides in insertion order
+    pub fn remove_left(&mut self, a: &A) -> Vec<B> {
+        let removed = self
+            .left
+            .drain((Bound::Included(a), Bound::Included(a)))
+            .map(|(_, b)| b)
+            .collect::<Vec<_>>();
+
+        for b in &removed {
+            self.right.remove(b, a);
+        }
+        removed
+    }
+
+    /// Removes every pair with `b` on the right, returning the left sides in insertion order
+    pub fn remove_right(&mut self, b: &B) -> Vec<A> {
+        let removed = self
+            .right
+            .drain((Bound::Included(b), Bound::Included(b)))
+            .map(|(_, a)| a)
+            .collect::<Vec<_>>();
+
+        for a in &removed {
+            self.left.remove(a, b);
+        }
+        removed
+    }
+
+    /// Returns `true` if the pair exists
+    pub fn contains(&self, a: &A, b: &B) -> bool {
+        self.left.values_of(a).contains(b)
+    }
+
+    /// Returns the right sides paired with `a`
+    pub fn values_of_left(&self, a: &A) -> &[B] {
+        self.left.values_of(a)
+    }
+
+    /// Returns the left sides paired with `b`
+    pub fn values_of_right(&self, b: &B) -> &[A] {
+        self.right.values_of(b)
+    }
+
+    /// Returns an iterator over the pairs with the left side in `range`, ordered by the left side
+    pub fn range_left<R>(&self, range: R) -> Tuples<'_, A, B>
+    where
+        R: RangeBounds<A>,
+    {
+        self.left.range(range)
+    }
+
+    /// Returns an iterator over the pairs with the right side in `range`, ordered by the right
+    /// side. The pairs are returned as `(B, A)`.
+    pub fn range_right<R>(&self, range: R) -> Tuples<'_, B, A>
+    where
+        R: RangeBounds<B>,
+    {
+        self.right.range(range)
+    }
+
+    /// Returns the pairs ordered by the left side
+    pub fn left(&self) -> &SortedList<A, B> {
+        &self.left
+    }
+
+    /// Returns the pairs ordered by the right side, as `(B, A)`
+    pub fn right(&self) -> &SortedList<B, A> {
+        &self.right
+    }
+
+    /// Removes all pairs
+    pub fn clear(&mut self) {
+        self.left.clear();
+        self.right.clear();
+    }
+}
+
+impl<A: Ord + Clone, B: Ord + Clone> Default for BiSortedList<A, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Ord + Clone, B: Ord + Clone> Clone for BiSortedList<A, B> {
+    fn clone(&self) -> Self {
+        BiSortedList {
+            left: self.left.clone(),
+            right: self.right.clone(),
+        }
+    }
+}
+
+impl<A: Ord + Clone, B: Ord + Clone> FromIterator<(A, B)> for BiSortedList<A, B> {
+    fn from_iter<T: IntoIterator<Item = (A, B)>>(iter: T) -> Self {
+        let mut this = Self::new();
+
+        for (a, b) in iter {
+            this.insert(a, b);
+        }
+
+        this
+    }
+}
+
+impl<A: Ord + fmt::Debug, B: Ord + fmt::Debug> fmt::Debug for BiSortedList<A, B> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "BiSortedList {{ {:?} }}", self.left.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BiSortedList;
+
+    fn sides_agree(list: &BiSortedList<u32, char>) {
+        let mut left = list
+            .left()
+            .iter()
+            .map(|(a, b)| (*a, *b))
+            .collect::<Vec<_>>();
+        let mut right = list
+            .right()
+            .iter()
+            .map(|(b, a)| (*a, *b))
+            .collect::<Vec<_>>();
+        left.sort();
+        right.sort();
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut list = BiSortedList::new();
+        assert!(list.insert(1u32, 'x'));
+        assert!(list.insert(2, 'x'));
+        assert!(list.insert(1, 'y'));
+        assert!(!list.insert(1, 'y'));
+        assert_eq!(list.len(), 3);
+
+        assert!(list.remove(&1, &'x'));
+        assert!(!list.remove(&1, &'x'));
+        assert!(!list.contains(&1, &'x'));
+        assert_eq!(list.values_of_right(&'x'), &[2]);
+        assert_eq!(list.values_of_left(&1), &['y']);
+        sides_agree(&list);
+    }
+
+    #[test]
+    fn ranges() {
+        let list = (0..20u32)
+            .map(|i| (i, (b'a' + (i % 5) as u8) as char))
+            .collect::<BiSortedList<_, _>>();
+
+        assert_eq!(
+            list.range_left(3..6)
+                .map(|(a, b)| (*a, *b))
+                .collect::<Vec<_>>(),
+            vec![(3, 'd'), (4, 'e'), (5, 'a')]
+        );
+        assert_eq!(
+            list.range_right('b'..='b')
+                .map(|(_, a)| *a)
+                .collect::<Vec<_>>(),
+            vec![1, 6, 11, 16]
+        );
+    }
+
+    #[test]
+    fn remove_whole_keys() {
+        let mut list = (0..20u32)
+            .map(|i| (i % 4, (b'a' + (i % 5) as u8) as char))
+            .collect::<BiSortedList<_, _>>();
+
+        assert_eq!(list.remove_left(&1), vec!['b', 'a', 'e', 'd', 'c']);
+        assert_eq!(list.values_of_left(&1).len(), 0);
+        sides_agree(&list);
+
+        assert_eq!(list.remove_right(&'a'), vec![0, 2, 3]);
+        assert_eq!(list.values_of_right(&'a').len(), 0);
+        sides_agree(&list);
+
+        assert_eq!(list.len(), 12);
+    }
+}
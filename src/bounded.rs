@@ -0,0 +1,252 @@
+//! `SortedList` with a maximum number of tuples.
+
+use std::fmt;
+use std::ops::Deref;
+
+use super::{ResultExt, SortedList};
+
+/// Function choosing the index of the tuple to evict, given the list and the index of the
+/// inserted tuple
+pub type EvictFn<K, V> = Box<dyn FnMut(&SortedList<K, V>, usize) -> usize + Send>;
+
+/// Decides which tuple a full `BoundedSortedList` evicts after an insertion
+pub enum EvictionPolicy<K: Ord, V: PartialEq> {
+    /// Evicts the first value of the smallest key, keeping the largest keys
+    SmallestKey,
+    /// Evicts the last value of the largest key, keeping the smallest keys
+    LargestKey,
+    /// Evicts the oldest value of the inserted key. When the key has no other values the new
+    /// tuple itself is evicted.
+    OldestInGroup,
+    /// Calls the function with the list and the index of the inserted tuple, evicting the tuple
+    /// at the returned index
+    Custom(EvictFn<K, V>),
+}
+
+impl<K: Ord, V: PartialEq> EvictionPolicy<K, V> {
+    /// Creates a `Custom` policy from the function
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: FnMut(&SortedList<K, V>, usize) -> usize + Send + 'static,
+    {
+        EvictionPolicy::Custom(Box::new(f))
+    }
+
+    fn victim(&mut self, list: &SortedList<K, V>, inserted: usize) -> usize {
+        match *self {
+            EvictionPolicy::SmallestKey => 0,
+            EvictionPolicy::LargestKey => list.len() - 1,
+            EvictionPolicy::OldestInGroup => {
+                let key = &list.keys[inserted];
+                list.find_first_position(key).either()
+            }
+            EvictionPolicy::Custom(ref mut f) => f(list, inserted),
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> fmt::Debug for EvictionPolicy<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EvictionPolicy::SmallestKey => write!(fmt, "SmallestKey"),
+            EvictionPolicy::LargestKey => write!(fmt, "LargestKey"),
+            EvictionPolicy::OldestInGroup => write!(fmt, "OldestInGroup"),
+            EvictionPolicy::Custom(_) => write!(fmt, "Custom"),
+        }
+    }
+}
+
+/// Result of `BoundedSortedList::insert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertOutcome<K, V> {
+    /// The tuple was inserted without evicting anything
+    Inserted,
+    /// The tuple was inserted and the returned tuple evicted to make room for it
+    Evicted(K, V),
+    /// The policy chose the new tuple for eviction, so the list is unchanged
+    Rejected(K, V),
+    /// The same tuple already exists, so the list is unchanged
+    Duplicate,
+}
+
+impl<K, V> InsertOutcome<K, V> {
+    /// Returns `true` if the new tuple is in the list
+    pub fn is_inserted(&self) -> bool {
+        match *self {
+            InsertOutcome::Inserted | InsertOutcome::Evicted(..) => true,
+            InsertOutcome::Rejected(..) | InsertOutcome::Duplicate => false,
+        }
+    }
+}
+
+/// `SortedList` holding at most a given number of tuples, evicting tuples chosen by an
+/// `EvictionPolicy` when an insertion goes over the limit.
+///
+/// All of the read-only methods of `SortedList` are available through `Deref`.
+///
+/// # Example
+///
+/// Keeping the three best scores:
+///
+/// ```
+/// use sorted_list::{BoundedSortedList, EvictionPolicy, InsertOutcome};
+///
+/// let mut best = BoundedSortedList::with_limit(3, EvictionPolicy::SmallestKey);
+/// best.insert(10u32, "a");
+/// best.insert(30, "b");
+/// best.insert(20, "c");
+///
+/// assert_eq!(best.insert(40, "d"), InsertOutcome::Evicted(10, "a"));
+/// assert_eq!(best.insert(5, "e"), InsertOutcome::Rejected(5, "e"));
+/// assert_eq!(best.keys().collect::<Vec<_>>(), vec![&20, &30, &40]);
+/// ```
+pub struct BoundedSortedList<K: Ord, V: PartialEq> {
+    list: SortedList<K, V>,
+    limit: usize,
+    policy: EvictionPolicy<K, V>,
+}
+
+impl<K: Ord, V: PartialEq> BoundedSortedList<K, V> {
+    /// Creates a new empty list holding at most `limit` tuples
+    pub fn with_limit(limit: usize, policy: EvictionPolicy<K, V>) -> Self {
+        BoundedSortedList {
+            list: SortedList::with_capacity(limit.saturating_add(1)),
+            limit,
+            policy,
+        }
+    }
+
+    /// Returns the maximum number of tuples
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Inserts the tuple, evicting a tuple chosen by the policy if the list was full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a `Custom` policy returns an index out of bounds.
+    pub fn insert(&mut self, key: K, value: V) -> InsertOutcome<K, V> {
+        let inserted = match self.list.insert_index(key, value) {
+            Some(index) => index,
+            None => return InsertOutcome::Duplicate,
+        };
+
+        if self.list.len() <= self.limit {
+            return InsertOutcome::Inserted;
+        }
+
+        let victim = self.policy.victim(&self.list, inserted);
+        assert!(victim < self.list.len(), "evicted index out of bounds");
+
+        let (key, value) = self.list.remove_at(victim);
+        if victim == inserted {
+            InsertOutcome::Rejected(key, value)
+        } else {
+            InsertOutcome::Evicted(key, value)
+        }
+    }
+
+    /// Removes the tuple, see `SortedList::remove`
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        self.list.remove(key, value)
+    }
+
+    /// Removes and returns the first tuple, see `SortedList::pop_first`
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.list.pop_first()
+    }
+
+    /// Removes and returns the last tuple, see `SortedList::pop_last`
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.list.pop_last()
+    }
+
+    /// Returns the list, dropping the limit
+    pub fn into_inner(self) -> SortedList<K, V> {
+        self.list
+    }
+}
+
+impl<K: Ord, V: PartialEq> Deref for BoundedSortedList<K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for BoundedSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "BoundedSortedList {{ limit: {}, policy: {:?}, list: {:?} }}",
+            self.limit, self.policy, self.list
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundedSortedList, EvictionPolicy, InsertOutcome};
+
+    fn to_vec(list: &BoundedSortedList<u32, u32>) -> Vec<(u32, u32)> {
+        list.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    #[test]
+    fn largest_key() {
+        let mut list = BoundedSortedList::with_limit(2, EvictionPolicy::LargestKey);
+
+        assert_eq!(list.insert(5, 0), InsertOutcome::Inserted);
+        assert_eq!(list.insert(3, 0), InsertOutcome::Inserted);
+        assert_eq!(list.insert(3, 0), InsertOutcome::Duplicate);
+        assert_eq!(list.insert(4, 0), InsertOutcome::Evicted(5, 0));
+        assert_eq!(list.insert(4, 1), InsertOutcome::Rejected(4, 1));
+        assert_eq!(list.insert(1, 1), InsertOutcome::Evicted(4, 0));
+
+        assert_eq!(to_vec(&list), vec![(1, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn oldest_in_group() {
+        let mut list = BoundedSortedList::with_limit(3, EvictionPolicy::OldestInGroup);
+
+        list.insert(1, 0);
+        list.insert(1, 1);
+        list.insert(2, 0);
+
+        assert_eq!(list.insert(1, 2), InsertOutcome::Evicted(1, 0));
+        assert_eq!(list.insert(3, 0), InsertOutcome::Rejected(3, 0));
+        assert!(!list.insert(3, 0).is_inserted());
+
+        assert_eq!(to_vec(&list), vec![(1, 1), (1, 2), (2, 0)]);
+    }
+
+    #[test]
+    fn custom_policy() {
+        // evicts the smallest value regardless of the key
+        let policy = EvictionPolicy::custom(|list: &super::SortedList<u32, u32>, _| {
+            list.values()
+                .enumerate()
+                .min_by_key(|&(_, v)| *v)
+                .map(|(i, _)| i)
+                .unwrap()
+        });
+        let mut list = BoundedSortedList::with_limit(2, policy);
+
+        list.insert(1, 10);
+        list.insert(2, 5);
+        assert_eq!(list.insert(3, 7), InsertOutcome::Evicted(2, 5));
+        assert_eq!(list.insert(0, 1), InsertOutcome::Rejected(0, 1));
+
+        assert_eq!(to_vec(&list), vec![(1, 10), (3, 7)]);
+    }
+
+    #[test]
+    fn zero_limit() {
+        let mut list = BoundedSortedList::with_limit(0, EvictionPolicy::SmallestKey);
+        assert_eq!(list.insert(1, 1), InsertOutcome::Rejected(1, 1));
+        assert!(list.is_empty());
+    }
+}
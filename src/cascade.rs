@@ -0,0 +1,217 @@
+//! Searching the same key in many lists with fractional cascading.
+
+use std::fmt;
+use std::ops::Range;
+
+use super::SortedList;
+
+/// One list of the cascade, with every other key of the next level merged into its own keys.
+struct Level<'a, K> {
+    /// Distinct keys of the list merged with every other key of the next level, in order
+    keys: Vec<&'a K>,
+    /// Range of every key of `keys` in the list, empty at its insertion point if the key came
+    /// from the next level, with `(len, len)` for keys past the end
+    own: Vec<(usize, usize)>,
+    /// Position of every key of `keys` in the keys of the next level, plus one past the end
+    next: Vec<usize>,
+}
+
+/// Searches a key in a fixed set of `SortedList`s at once, in `O(log n + k)` for `k` lists
+/// instead of `k` separate binary searches.
+///
+/// Built once over the lists using fractional cascading: the keys of every list are
+/// merged with every other key of the next one, so that one binary search in the first list
+/// leads to the position in every other list in a step or two.
+///
+/// The lists are borrowed, so they cannot change while the search exists.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::{CascadedSearch, SortedList};
+///
+/// let roads = vec![(3u32, "a1"), (7, "a7")].into_iter().collect::<SortedList<_, _>>();
+/// let rivers = vec![(5u32, "loire")].into_iter().collect::<SortedList<_, _>>();
+/// let towns = vec![(7u32, "ely"), (7, "ouse")].into_iter().collect::<SortedList<_, _>>();
+///
+/// let search = CascadedSearch::new(vec![&roads, &rivers, &towns]);
+///
+/// let empty: &[&str] = &[];
+/// assert_eq!(search.values_of(&7), vec![&["a7"][..], empty, &["ely", "ouse"][..]]);
+/// assert_eq!(search.ranges(&6), vec![1..1, 1..1, 0..0]);
+/// ```
+pub struct CascadedSearch<'a, K: Ord, V: PartialEq> {
+    lists: Vec<&'a SortedList<K, V>>,
+    levels: Vec<Level<'a, K>>,
+}
+
+impl<'a, K: Ord, V: PartialEq> CascadedSearch<'a, K, V> {
+    /// Builds the search over the lists, which are searched in the given order
+    pub fn new<I>(lists: I) -> Self
+    where
+        I: IntoIterator<Item = &'a SortedList<K, V>>,
+    {
+        let lists = lists.into_iter().collect::<Vec<_>>();
+        let mut levels = Vec::<Level<'a, K>>::with_capacity(lists.len());
+
+        for list in lists.iter().rev() {
+            let level = Level::build(list.key_slice(), levels.last());
+            levels.push(level);
+        }
+        levels.reverse();
+
+        CascadedSearch { lists, levels }
+    }
+
+    /// Returns the number of lists
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    /// Returns `true` if the search was built over no lists
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// Returns the range of positions of `key` in every list, as used by `Cursor::index`. The
+    /// range is empty at the position where `key` would be inserted if a list does not have it.
+    pub fn ranges(&self, key: &K) -> Vec<Range<usize>> {
+        let mut ranges = Vec::with_capacity(self.levels.len());
+        let mut at = match self.levels.first() {
+            Some(level) => level.keys.partition_point(|k| *k < key),
+            None => return ranges,
+        };
+
+        for level in &self.levels {
+            // only keys which were not carried up from this level can be skipped over, and no
+            // two of them are next to each other
+            while at > 0 && level.keys[at - 1] >= key {
+                at -= 1;
+            }
+
+            let (start, end) = level.own[at];
+            if level.keys.get(at).is_some_and(|k| *k == key) {
+                ranges.push(start..end);
+            } else {
+                ranges.push(start..start);
+            }
+
+            at = level.next[at];
+        }
+
+        ranges
+    }
+
+    /// Returns the values of `key` in every list, see `SortedList::values_of`
+    pub fn values_of(&self, key: &K) -> Vec<&'a [V]> {
+        self.ranges(key)
+            .into_iter()
+            .zip(&self.lists)
+            .map(|(range, list)| &list.value_slice()[range])
+            .collect()
+    }
+}
+
+impl<'a, K: Ord> Level<'a, K> {
+    fn build(own_keys: &'a [K], next: Option<&Level<'a, K>>) -> Self {
+        let carried = next.map_or(&[][..], |next| &next.keys[..]);
+
+        // merge the distinct keys of the list with every other key of the next level
+        let mut keys = Vec::with_capacity(own_keys.len() + carried.len() / 2 + 1);
+        let mut own = own_keys.iter().peekable();
+        let mut up = carried.iter().step_by(2).cloned().peekable();
+        loop {
+            let key = match (own.peek(), up.peek()) {
+                (Some(a), Some(b)) if *a <= *b => own.next().unwrap(),
+                (_, Some(_)) => up.next().unwrap(),
+                (Some(_), None) => own.next().unwrap(),
+                (None, None) => break,
+            };
+
+            if keys.last().is_some_and(|last| *last == key) {
+                continue;
+            }
+            keys.push(key);
+        }
+
+        let mut own_ranges = Vec::with_capacity(keys.len() + 1);
+        let mut start = 0;
+        for key in &keys {
+            start += own_keys[start..].partition_point(|k| k < *key);
+            let end = start + own_keys[start..].partition_point(|k| k == *key);
+            own_ranges.push((start, end));
+        }
+        own_ranges.push((own_keys.len(), own_keys.len()));
+
+        let mut next_positions = Vec::with_capacity(keys.len() + 1);
+        let mut at = 0;
+        for key in &keys {
+            while at < carried.len() && carried[at] < *key {
+                at += 1;
+            }
+            next_positions.push(at);
+        }
+        next_positions.push(carried.len());
+
+        Level {
+            keys,
+            own: own_ranges,
+            next: next_positions,
+        }
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq> fmt::Debug for CascadedSearch<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "CascadedSearch {{ lists: {} }}", self.lists.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::CascadedSearch;
+
+    fn lists() -> Vec<SortedList<u32, u32>> {
+        (1..8u32)
+            .map(|step| {
+                (0..200u32)
+                    .map(|i| (i * step % 300, i))
+                    .filter(|&(k, _)| k % (step + 1) != 0)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_separate_searches() {
+        let lists = lists();
+        let search = CascadedSearch::new(&lists);
+        assert_eq!(search.len(), 7);
+
+        for key in 0..310 {
+            let expected = lists.iter().map(|l| l.values_of(&key)).collect::<Vec<_>>();
+            assert_eq!(search.values_of(&key), expected, "key {}", key);
+
+            for (range, list) in search.ranges(&key).into_iter().zip(&lists) {
+                assert_eq!(range.start, list.partition_point(|k| *k < key));
+                assert_eq!(range.end, list.partition_point(|k| *k <= key));
+            }
+        }
+    }
+
+    #[test]
+    fn empty_lists() {
+        let search = CascadedSearch::<u32, u32>::new(vec![]);
+        assert!(search.is_empty());
+        assert!(search.values_of(&1).is_empty());
+
+        let empty = SortedList::new();
+        let full = vec![(1u32, 1u32), (2, 2)]
+            .into_iter()
+            .collect::<SortedList<_, _>>();
+        let search = CascadedSearch::new(vec![&empty, &full, &empty]);
+        assert_eq!(search.ranges(&2), vec![0..0, 1..2, 0..0]);
+        assert_eq!(search.ranges(&3), vec![0..0, 2..2, 0..0]);
+    }
+}
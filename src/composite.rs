@@ -0,0 +1,175 @@
+//! Range queries on a prefix of tuple keys.
+
+use std::ops::{Bound, RangeBounds};
+
+use super::{SortedList, Tuples};
+
+/// Tuple key ordered lexicographically, allowing queries on its leading fields.
+///
+/// Implemented for tuples of two to four fields.
+pub trait TupleKey: Ord {
+    /// Type of the first field
+    type First: Ord;
+    /// Type of the second field
+    type Second: Ord;
+
+    /// Returns the first field
+    fn first(&self) -> &Self::First;
+
+    /// Returns the second field
+    fn second(&self) -> &Self::Second;
+}
+
+macro_rules! tuple_key {
+    ($a:ident, $b:ident $(, $rest:ident)*) => {
+        impl<$a: Ord, $b: Ord $(, $rest: Ord)*> TupleKey for ($a, $b $(, $rest)*) {
+            type First = $a;
+            type Second = $b;
+
+            fn first(&self) -> &$a {
+                &self.0
+            }
+
+            fn second(&self) -> &$b {
+                &self.1
+            }
+        }
+    };
+}
+
+tuple_key!(A, B);
+tuple_key!(A, B, C);
+tuple_key!(A, B, C, D);
+
+impl<K: TupleKey, V: PartialEq> SortedList<K, V> {
+    /// Returns an iterator over the tuples whose key starts with `first`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let mut list = SortedList::new();
+    /// list.insert(("acme", 10u64), "login");
+    /// list.insert(("acme", 25), "logout");
+    /// list.insert(("acme", 40), "login");
+    /// list.insert(("globex", 15), "login");
+    ///
+    /// assert_eq!(list.range_prefix(&"acme").count(), 3);
+    /// assert_eq!(
+    ///     list.range_prefix_then(&"acme", 20..50).map(|(_, v)| *v).collect::<Vec<_>>(),
+    ///     vec!["logout", "login"]);
+    /// ```
+    pub fn range_prefix(&self, first: &K::First) -> Tuples<'_, K, V> {
+        self.range_prefix_then(first, ..)
+    }
+
+    /// Returns an iterator over the tuples whose key starts with `first` and has its second
+    /// field in `range`
+    pub fn range_prefix_then<R>(&self, first: &K::First, range: R) -> Tuples<'_, K, V>
+    where
+        R: RangeBounds<K::Second>,
+    {
+        let keys = self.key_slice();
+        let start = keys.partition_point(|k| k.first() < first);
+        let end = start + keys[start..].partition_point(|k| k.first() == first);
+        let prefix = &keys[start..end];
+
+        let low = match range.start_bound() {
+            Bound::Included(b) => prefix.partition_point(|k| k.second() < b),
+            Bound::Excluded(b) => prefix.partition_point(|k| k.second() <= b),
+            Bound::Unbounded => 0,
+        };
+
+        let high = match range.end_bound() {
+            Bound::Included(b) => prefix.partition_point(|k| k.second() <= b),
+            Bound::Excluded(b) => prefix.partition_point(|k| k.second() < b),
+            Bound::Unbounded => prefix.len(),
+        };
+
+        Tuples {
+            keys,
+            values: self.value_slice(),
+            low: start + low,
+            high: start + high.max(low),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use std::ops::Bound;
+
+    fn events() -> SortedList<(u32, u64), u32> {
+        let mut list = SortedList::new();
+        for tenant in 0..5u32 {
+            for t in 0..10u64 {
+                list.insert((tenant, t * 10), tenant * 100 + t as u32);
+            }
+        }
+        list
+    }
+
+    fn values<'a, I: Iterator<Item = (&'a (u32, u64), &'a u32)>>(iter: I) -> Vec<u32> {
+        iter.map(|(_, v)| *v).collect()
+    }
+
+    #[test]
+    fn prefix() {
+        let list = events();
+
+        assert_eq!(values(list.range_prefix(&0)), (0..10).collect::<Vec<_>>());
+        assert_eq!(
+            values(list.range_prefix(&4)),
+            (400..410).collect::<Vec<_>>()
+        );
+        assert_eq!(list.range_prefix(&5).count(), 0);
+    }
+
+    #[test]
+    fn prefix_then() {
+        let list = events();
+
+        assert_eq!(
+            values(list.range_prefix_then(&2, 20..50)),
+            vec![202, 203, 204]
+        );
+        assert_eq!(
+            values(list.range_prefix_then(&2, 20..=50)),
+            vec![202, 203, 204, 205]
+        );
+        assert_eq!(values(list.range_prefix_then(&2, 85..)), vec![209]);
+        assert_eq!(values(list.range_prefix_then(&2, ..10)), vec![200]);
+        assert_eq!(
+            values(list.range_prefix_then(&3, (Bound::Excluded(80), Bound::Unbounded))),
+            vec![309]
+        );
+        assert_eq!(
+            list.range_prefix_then(&2, (Bound::Included(50), Bound::Excluded(20)))
+                .count(),
+            0
+        );
+        assert_eq!(list.range_prefix_then(&9, ..).count(), 0);
+    }
+
+    #[test]
+    fn longer_tuples() {
+        let list = vec![
+            (("eu", 2u32, 'b'), 1),
+            (("eu", 1, 'a'), 2),
+            (("us", 1, 'a'), 3),
+            (("eu", 1, 'c'), 4),
+        ]
+        .into_iter()
+        .collect::<SortedList<_, _>>();
+
+        assert_eq!(
+            list.range_prefix_then(&"eu", 1..=1)
+                .map(|(_, v)| *v)
+                .collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+        assert_eq!(list.range_prefix(&"us").count(), 1);
+    }
+}
@@ -0,0 +1,685 @@
+//! Sorted list storing string and byte keys front coded.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+use super::{ResultExt, SortedList};
+
+/// Number of keys after which a new block is started when encoding. Blocks are split once they
+/// grow to twice this long.
+const RESTART_INTERVAL: usize = 16;
+
+/// Key which can be stored as bytes in a `CompressedSortedList`.
+///
+/// The keys must be ordered exactly like their bytes are.
+pub trait ByteKey: Ord + Clone {
+    /// Returns the bytes of the key
+    fn key_bytes(&self) -> &[u8];
+
+    /// Creates the key back from the bytes returned by `key_bytes`
+    fn from_key_bytes(bytes: Vec<u8>) -> Self;
+}
+
+impl ByteKey for String {
+    fn key_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    fn from_key_bytes(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes).expect("bytes of a String")
+    }
+}
+
+impl ByteKey for Vec<u8> {
+    fn key_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_key_bytes(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
+/// Sorted list of `(K, V)` tuples which stores every distinct key once, front coded against the
+/// previous key.
+///
+/// The keys are kept in blocks in a single buffer. The first key of every block is a restart
+/// point stored in full, and every other key only stores the length of the prefix it shares
+/// with the previous key and the rest of its bytes. Lookups binary search the restart points and
+/// then decode a single block, and iteration decodes the keys lazily one at a time. Keys sharing
+/// long prefixes, such as paths and URLs, take a fraction of the memory of `String`s.
+///
+/// Values are kept in insertion order like in `SortedList`. As the keys are not stored as such,
+/// the iterators return decoded copies of them.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::CompressedSortedList;
+///
+/// let mut list = CompressedSortedList::new();
+/// list.insert("/usr/lib/libc.so".to_string(), 1);
+/// list.insert("/usr/lib/libm.so".to_string(), 2);
+/// list.insert("/usr/bin/env".to_string(), 3);
+///
+/// assert_eq!(list.values_of(&"/usr/lib/libm.so".to_string()), &[2]);
+/// assert_eq!(
+///     list.range("/usr/lib".to_string()..).map(|(k, _)| k).collect::<Vec<_>>(),
+///     vec!["/usr/lib/libc.so", "/usr/lib/libm.so"]);
+/// ```
+pub struct CompressedSortedList<K, V> {
+    /// front coded keys, the blocks following each other
+    data: Vec<u8>,
+    blocks: Vec<Block>,
+    /// values of the `i`th key are `values[groups[i]..groups[i + 1]]`
+    groups: Vec<usize>,
+    values: Vec<V>,
+    marker: PhantomData<K>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    /// offset of the restart point in `data`
+    offset: usize,
+    /// index of the first key of the block
+    first: usize,
+}
+
+impl<K: ByteKey, V: PartialEq> CompressedSortedList<K, V> {
+    /// Creates a new empty list
+    pub fn new() -> Self {
+        CompressedSortedList {
+            data: Vec::new(),
+            blocks: Vec::new(),
+            groups: vec![0],
+            values: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of tuples
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if there are no tuples
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the number of distinct keys
+    pub fn key_count(&self) -> usize {
+        self.groups.len() - 1
+    }
+
+    /// Returns the number of bytes used by the encoded keys
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Inserts the tuple, returning `false` if it already exists
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        let block = if self.blocks.is_empty() {
+            // an empty block for the first key to go to
+            self.blocks.push(Block {
+                offset: 0,
+                first: 0,
+            });
+            0
+        } else {
+            self.block_of(key.key_bytes())
+        };
+        let mut keys = self.decode_block(block);
+        let first = self.blocks[block].first;
+
+        match keys.binary_search_by(|k| k.as_slice().cmp(key.key_bytes())) {
+            Ok(at) => {
+                let index = first + at;
+                let end = self.groups[index + 1];
+                if self.values[self.groups[index]..end].contains(&value) {
+                    return false;
+                }
+
+                self.values.insert(end, value);
+                for group in &mut self.groups[index + 1..] {
+                    *group += 1;
+                }
+            }
+            Err(at) => {
+                // rewrite before the groups change, as they tell the length of the last block
+                keys.insert(at, key.key_bytes().to_vec());
+                self.rewrite_block(block, &keys);
+
+                let index = first + at;
+                let start = self.groups[index];
+                self.values.insert(start, value);
+                self.groups.insert(index + 1, start);
+                for group in &mut self.groups[index + 1..] {
+                    *group += 1;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Removes the tuple, returning `true` if it existed
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        let index = match self.find(key.key_bytes()) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        let start = self.groups[index];
+        let at = match self.values[start..self.groups[index + 1]]
+            .iter()
+            .position(|v| v == value)
+        {
+            Some(at) => start + at,
+            None => return false,
+        };
+
+        self.values.remove(at);
+        for group in &mut self.groups[index + 1..] {
+            *group -= 1;
+        }
+
+        if self.groups[index] == self.groups[index + 1] {
+            // that was the last value of the key
+            let block = self.block_of(key.key_bytes());
+            let mut keys = self.decode_block(block);
+            keys.remove(index - self.blocks[block].first);
+            self.rewrite_block(block, &keys);
+
+            self.groups.remove(index + 1);
+        }
+
+        true
+    }
+
+    /// Returns the values of a specific key as a slice, in insertion order
+    pub fn values_of(&self, key: &K) -> &[V] {
+        match self.find(key.key_bytes()) {
+            Ok(index) => &self.values[self.groups[index]..self.groups[index + 1]],
+            Err(_) => &[],
+        }
+    }
+
+    /// Returns `true` if the key has at least one value
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key.key_bytes()).is_ok()
+    }
+
+    /// Iterates all stored tuples in order, decoding the keys as it goes
+    pub fn iter(&self) -> CompressedTuples<'_, K, V> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the specified range of tuples
+    pub fn range<R>(&self, range: R) -> CompressedTuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let low = match range.start_bound() {
+            Bound::Included(key) => self.find(key.key_bytes()).either(),
+            Bound::Excluded(key) => self.find(key.key_bytes()).map(|i| i + 1).either(),
+            Bound::Unbounded => 0,
+        };
+
+        let high = match range.end_bound() {
+            Bound::Included(key) => self.find(key.key_bytes()).map(|i| i + 1).either(),
+            Bound::Excluded(key) => self.find(key.key_bytes()).either(),
+            Bound::Unbounded => self.key_count(),
+        };
+
+        CompressedTuples::new(self, low, high.max(low))
+    }
+
+    /// Returns the index of the block the key is in or would be inserted to
+    fn block_of(&self, key: &[u8]) -> usize {
+        let after = self
+            .blocks
+            .partition_point(|block| self.restart_key(block) <= key);
+        after.saturating_sub(1)
+    }
+
+    fn restart_key(&self, block: &Block) -> &[u8] {
+        let mut at = block.offset;
+        let shared = read_varint(&self.data, &mut at);
+        debug_assert_eq!(shared, 0);
+        let len = read_varint(&self.data, &mut at);
+        &self.data[at..at + len]
+    }
+
+    /// Returns the index of the key, or the index it would be inserted at
+    fn find(&self, key: &[u8]) -> Result<usize, usize> {
+        if self.blocks.is_empty() {
+            return Err(0);
+        }
+
+        let block = self.block_of(key);
+        let end = self.block_end(block);
+        let mut decoder = Decoder::new(&self.data, self.blocks[block].offset);
+
+        for index in self.blocks[block].first..end {
+            match decoder.next_key().cmp(key) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Ok(index),
+                Ordering::Greater => return Err(index),
+            }
+        }
+
+        Err(end)
+    }
+
+    /// Returns the index after the last key of the block
+    fn block_end(&self, block: usize) -> usize {
+        self.blocks
+            .get(block + 1)
+            .map_or(self.key_count(), |next| next.first)
+    }
+
+    fn decode_block(&self, block: usize) -> Vec<Vec<u8>> {
+        let end = self.block_end(block);
+        let Block { offset, first } = self.blocks[block];
+        let mut decoder = Decoder::new(&self.data, offset);
+
+        (first..end).map(|_| decoder.next_key().to_vec()).collect()
+    }
+
+    /// Replaces the encoded keys of the block with `keys`, splitting it if it grew too long and
+    /// dropping it if it became empty
+    fn rewrite_block(&mut self, block: usize, keys: &[Vec<u8>]) {
+        let Block { offset, first } = self.blocks[block];
+        let end = self
+            .blocks
+            .get(block + 1)
+            .map_or(self.data.len(), |b| b.offset);
+
+        let chunk = if keys.len() >= 2 * RESTART_INTERVAL {
+            (keys.len() + 1) / 2
+        } else {
+            keys.len().max(1)
+        };
+
+        let mut encoded = Vec::new();
+        let mut new_blocks = Vec::new();
+        let mut next_first = first;
+        for chunk in keys.chunks(chunk) {
+            new_blocks.push(Block {
+                offset: offset + encoded.len(),
+                first: next_first,
+            });
+            encode_block(&mut encoded, chunk);
+            next_first += chunk.len();
+        }
+
+        let old_keys = self.block_end(block) - first;
+        let key_delta = keys.len() as isize - old_keys as isize;
+        let byte_delta = encoded.len() as isize - (end - offset) as isize;
+
+        self.data.splice(offset..end, encoded);
+        self.blocks
+            .splice(block..=block, new_blocks.iter().cloned());
+
+        for b in &mut self.blocks[block + new_blocks.len()..] {
+            b.offset = (b.offset as isize + byte_delta) as usize;
+            b.first = (b.first as isize + key_delta) as usize;
+        }
+    }
+}
+
+fn encode_block(out: &mut Vec<u8>, keys: &[Vec<u8>]) {
+    let mut prev: &[u8] = &[];
+    for key in keys {
+        let shared = prev
+            .iter()
+            .zip(key.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        write_varint(out, shared);
+        write_varint(out, key.len() - shared);
+        out.extend_from_slice(&key[shared..]);
+        prev = key;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(data: &[u8], at: &mut usize) -> usize {
+    let mut n = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*at];
+        *at += 1;
+        n |= usize::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return n;
+        }
+        shift += 7;
+    }
+}
+
+/// Decodes keys one after another, across block boundaries
+struct Decoder<'a> {
+    data: &'a [u8],
+    at: usize,
+    key: Vec<u8>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8], at: usize) -> Self {
+        Decoder {
+            data,
+            at,
+            key: Vec::new(),
+        }
+    }
+
+    fn next_key(&mut self) -> &[u8] {
+        let shared = read_varint(self.data, &mut self.at);
+        let len = read_varint(self.data, &mut self.at);
+
+        self.key.truncate(shared);
+        self.key
+            .extend_from_slice(&self.data[self.at..self.at + len]);
+        self.at += len;
+        &self.key
+    }
+}
+
+impl<K: ByteKey, V: PartialEq> Default for CompressedSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: ByteKey, V: PartialEq> From<SortedList<K, V>> for CompressedSortedList<K, V> {
+    /// Encodes all of the keys in a single pass
+    fn from(list: SortedList<K, V>) -> Self {
+        let mut keys: Vec<Vec<u8>> = Vec::new();
+        let mut groups = vec![0];
+        let mut values = Vec::with_capacity(list.len());
+
+        for (key, value) in list {
+            if keys.last().map(|k| k.as_slice()) != Some(key.key_bytes()) {
+                keys.push(key.key_bytes().to_vec());
+                groups.push(values.len());
+            }
+            values.push(value);
+            *groups.last_mut().unwrap() = values.len();
+        }
+
+        let mut data = Vec::new();
+        let mut blocks = Vec::new();
+        for (n, chunk) in keys.chunks(RESTART_INTERVAL).enumerate() {
+            blocks.push(Block {
+                offset: data.len(),
+                first: n * RESTART_INTERVAL,
+            });
+            encode_block(&mut data, chunk);
+        }
+
+        CompressedSortedList {
+            data,
+            blocks,
+            groups,
+            values,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K: ByteKey, V: PartialEq> FromIterator<(K, V)> for CompressedSortedList<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self::from(iter.into_iter().collect::<SortedList<_, _>>())
+    }
+}
+
+impl<K: ByteKey + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for CompressedSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "CompressedSortedList {{ ")?;
+        fmt.debug_list().entries(self.iter()).finish()?;
+        write!(fmt, " }}")
+    }
+}
+
+/// Iterator over the tuples of a `CompressedSortedList`, decoding the keys lazily
+pub struct CompressedTuples<'a, K: 'a, V: 'a> {
+    list: &'a CompressedSortedList<K, V>,
+    decoder: Decoder<'a>,
+    /// index of the next key to decode
+    next_key: usize,
+    key: Option<K>,
+    /// index of the next value
+    value: usize,
+    end: usize,
+}
+
+impl<'a, K: ByteKey, V: PartialEq> CompressedTuples<'a, K, V> {
+    /// Creates an iterator over the keys in `low..high`
+    fn new(list: &'a CompressedSortedList<K, V>, low: usize, high: usize) -> Self {
+        let mut iter = CompressedTuples {
+            list,
+            decoder: Decoder::new(&list.data, 0),
+            next_key: 0,
+            key: None,
+            value: list.groups[low],
+            end: list.groups[high],
+        };
+
+        if low < high {
+            // start from the restart point before `low`
+            let block = list.blocks[list.blocks.partition_point(|b| b.first <= low) - 1];
+            iter.decoder.at = block.offset;
+            iter.next_key = block.first;
+
+            while iter.next_key < low {
+                iter.decoder.next_key();
+                iter.next_key += 1;
+            }
+        }
+
+        iter
+    }
+}
+
+impl<'a, K: ByteKey, V: PartialEq> Iterator for CompressedTuples<'a, K, V> {
+    type Item = (K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.value >= self.end {
+            return None;
+        }
+
+        if self.key.is_none() || self.value >= self.list.groups[self.next_key] {
+            let bytes = self.decoder.next_key().to_vec();
+            self.key = Some(K::from_key_bytes(bytes));
+            self.next_key += 1;
+        }
+
+        let value = &self.list.values[self.value];
+        self.value += 1;
+        Some((self.key.clone().unwrap(), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.value;
+        (len, Some(len))
+    }
+}
+
+impl<'a, K: ByteKey, V: PartialEq> ExactSizeIterator for CompressedTuples<'a, K, V> {}
+
+impl<'a, K, V> fmt::Debug for CompressedTuples<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "CompressedTuples {{ remaining: {} }}",
+            self.end - self.value
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::CompressedSortedList;
+
+    fn paths() -> Vec<(String, u32)> {
+        let mut paths = Vec::new();
+        for dir in 0..20 {
+            for file in 0..30 {
+                paths.push((
+                    format!(
+                        "/srv/data/projects/project-{:02}/src/module_{:03}.rs",
+                        dir, file
+                    ),
+                    dir * 100 + file,
+                ));
+            }
+        }
+        paths.reverse();
+        paths
+    }
+
+    fn to_vec(list: &CompressedSortedList<String, u32>) -> Vec<(String, u32)> {
+        list.iter().map(|(k, v)| (k, *v)).collect()
+    }
+
+    #[test]
+    fn matches_sorted_list() {
+        let mut plain = SortedList::new();
+        let mut compressed = CompressedSortedList::new();
+
+        for (i, (path, value)) in paths().into_iter().enumerate() {
+            // a few keys get several values
+            if i % 7 == 0 {
+                plain.insert(path.clone(), value + 1);
+                compressed.insert(path.clone(), value + 1);
+            }
+            assert_eq!(
+                plain.insert(path.clone(), value),
+                compressed.insert(path, value)
+            );
+        }
+
+        let expected = plain
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect::<Vec<_>>();
+        assert_eq!(to_vec(&compressed), expected);
+        assert_eq!(compressed.len(), plain.len());
+
+        let bulk = CompressedSortedList::from(plain.clone());
+        assert_eq!(to_vec(&bulk), expected);
+
+        for (key, _) in plain.iter().step_by(13) {
+            assert_eq!(compressed.values_of(key), plain.values_of(key));
+            assert_eq!(bulk.values_of(key), plain.values_of(key));
+        }
+        assert_eq!(compressed.values_of(&"/srv".to_string()).len(), 0);
+    }
+
+    #[test]
+    fn ranges() {
+        let list = paths().into_iter().collect::<CompressedSortedList<_, _>>();
+        let plain = paths().into_iter().collect::<SortedList<_, _>>();
+
+        let low = "/srv/data/projects/project-03".to_string();
+        let high = "/srv/data/projects/project-05/src/module_010.rs".to_string();
+
+        let check = |got: Vec<(String, &u32)>, expected: Vec<(&String, &u32)>| {
+            let expected = expected
+                .into_iter()
+                .map(|(k, v)| (k.clone(), v))
+                .collect::<Vec<_>>();
+            assert_eq!(got, expected);
+        };
+
+        check(
+            list.range(low.clone()..high.clone()).collect(),
+            plain.range(low.clone()..high.clone()).collect(),
+        );
+        check(
+            list.range(low.clone()..=high.clone()).collect(),
+            plain.range(low.clone()..=high.clone()).collect(),
+        );
+        check(
+            list.range(..low.clone()).collect(),
+            plain.range(..low.clone()).collect(),
+        );
+        check(
+            list.range(high.clone()..).collect(),
+            plain.range(high.clone()..).collect(),
+        );
+        check(
+            list.range(high.clone()..low.clone()).collect(),
+            plain.range(high.clone()..low.clone()).collect(),
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut list = paths().into_iter().collect::<CompressedSortedList<_, _>>();
+        let mut plain = paths().into_iter().collect::<SortedList<_, _>>();
+
+        for (path, value) in paths().into_iter().step_by(3) {
+            assert_eq!(list.remove(&path, &value), plain.remove(&path, &value));
+            assert!(!list.remove(&path, &value));
+        }
+
+        let expected = plain
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect::<Vec<_>>();
+        assert_eq!(to_vec(&list), expected);
+        assert_eq!(list.key_count(), expected.len());
+
+        for (path, value) in paths() {
+            list.remove(&path, &value);
+        }
+        assert!(list.is_empty());
+        assert_eq!(list.encoded_len(), 0);
+    }
+
+    #[test]
+    fn compresses_shared_prefixes() {
+        let list = paths().into_iter().collect::<CompressedSortedList<_, _>>();
+        let raw = paths().iter().map(|(k, _)| k.len()).sum::<usize>();
+
+        assert!(
+            list.encoded_len() * 3 < raw,
+            "{} vs {}",
+            list.encoded_len(),
+            raw
+        );
+    }
+
+    #[test]
+    fn byte_keys() {
+        let mut list = CompressedSortedList::new();
+        list.insert(vec![1u8, 2, 3], 'a');
+        list.insert(vec![1, 2], 'b');
+        list.insert(vec![0xff], 'c');
+
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            vec![
+                (vec![1, 2], &'b'),
+                (vec![1, 2, 3], &'a'),
+                (vec![0xff], &'c')
+            ]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Read-mostly `SortedList` shared between threads.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use super::SortedList;
+
+/// A `SortedList` shared between threads where readers work on immutable snapshots.
+///
+/// Writers apply their changes to a private copy of the latest version and then publish it by
+/// swapping a pointer, so readers only ever wait for the pointer swap and never for a write in
+/// progress. Writes copy the whole list, so changes should be batched with `write` or `extend`
+/// rather than inserted one by one.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::ConcurrentSortedList;
+///
+/// let list = ConcurrentSortedList::new();
+/// list.insert(1u32, 'a');
+///
+/// let before = list.snapshot();
+///
+/// list.write(|list| {
+///     list.insert(0, 'b');
+///     list.insert(1, 'c');
+/// });
+///
+/// assert_eq!(before.len(), 1);
+/// assert_eq!(list.snapshot().values_of(&1), &['a', 'c']);
+/// assert_eq!(list.snapshot().version(), before.version() + 1);
+/// ```
+pub struct ConcurrentSortedList<K: Ord, V: PartialEq> {
+    current: RwLock<Snapshot<K, V>>,
+    writer: Mutex<()>,
+}
+
+impl<K: Ord + Clone, V: PartialEq + Clone> ConcurrentSortedList<K, V> {
+    /// Creates a new empty `ConcurrentSortedList`
+    pub fn new() -> Self {
+        Self::from(SortedList::new())
+    }
+
+    /// Returns the latest published version of the list
+    pub fn snapshot(&self) -> Snapshot<K, V> {
+        // the lock is only ever held for cloning or swapping the pointer, so a poisoned lock
+        // still holds a complete version
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the number of the latest published version
+    pub fn version(&self) -> u64 {
+        self.snapshot().version()
+    }
+
+    /// Applies `f` to a copy of the latest version and publishes the result as a new version.
+    /// Writers are serialized, readers can keep taking snapshots meanwhile.
+    ///
+    /// If `f` panics nothing is published.
+    pub fn write<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut SortedList<K, V>) -> R,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let latest = self.snapshot();
+        let mut next = (*latest.list).clone();
+
+        let ret = f(&mut next);
+
+        let next = Snapshot {
+            list: Arc::new(next),
+            version: latest.version + 1,
+        };
+
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = next;
+
+        ret
+    }
+
+    /// Inserts a single tuple and publishes a new version, see `SortedList::insert`
+    pub fn insert(&self, key: K, value: V) -> bool {
+        self.write(|list| list.insert(key, value))
+    }
+
+    /// Inserts all of the tuples and publishes them as a single new version
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.write(|list| list.extend(iter))
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq + Clone> Default for ConcurrentSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V: PartialEq> From<SortedList<K, V>> for ConcurrentSortedList<K, V> {
+    fn from(list: SortedList<K, V>) -> Self {
+        ConcurrentSortedList {
+            current: RwLock::new(Snapshot {
+                list: Arc::new(list),
+                version: 0,
+            }),
+            writer: Mutex::new(()),
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> fmt::Debug for ConcurrentSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        write!(
+            fmt,
+            "ConcurrentSortedList {{ version: {}, len: {} }}",
+            current.version,
+            current.list.len()
+        )
+    }
+}
+
+/// Immutable version of a `ConcurrentSortedList`, dereferences to `SortedList` for all of the
+/// read-only methods.
+pub struct Snapshot<K: Ord, V: PartialEq> {
+    list: Arc<SortedList<K, V>>,
+    version: u64,
+}
+
+impl<K: Ord, V: PartialEq> Snapshot<K, V> {
+    /// Returns the version number of this snapshot, starting from zero and growing by one on
+    /// every published write
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+impl<K: Ord, V: PartialEq> Deref for Snapshot<K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord, V: PartialEq> Clone for Snapshot<K, V> {
+    fn clone(&self) -> Self {
+        Snapshot {
+            list: Arc::clone(&self.list),
+            version: self.version,
+        }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for Snapshot<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "Snapshot {{ version: {}, list: {:?} }}",
+            self.version, self.list
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcurrentSortedList;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn snapshots_are_isolated() {
+        let list = ConcurrentSortedList::new();
+        list.insert(1u32, 1u8);
+        let first = list.snapshot();
+
+        list.extend(vec![(0, 0), (2, 2)]);
+        let second = list.snapshot();
+
+        assert_eq!(first.iter().collect::<Vec<_>>(), vec![(&1, &1)]);
+        assert_eq!(second.range(1..).count(), 2);
+        assert_eq!(first.version(), 1);
+        assert_eq!(second.version(), 2);
+    }
+
+    #[test]
+    fn panicking_writer_publishes_nothing() {
+        let list = Arc::new(ConcurrentSortedList::new());
+        list.insert(1u32, 1u8);
+
+        let cloned = Arc::clone(&list);
+        let res = thread::spawn(move || {
+            cloned.write(|list| {
+                list.insert(2, 2);
+                panic!("failed halfway");
+            })
+        })
+        .join();
+
+        assert!(res.is_err());
+        assert_eq!(list.version(), 1);
+        assert_eq!(list.snapshot().len(), 1);
+
+        // and the list is still writable
+        assert!(list.insert(2, 2));
+        assert_eq!(list.snapshot().len(), 2);
+    }
+
+    #[test]
+    fn readers_progress_during_write() {
+        let list = Arc::new(ConcurrentSortedList::new());
+        list.insert(0u32, 0u32);
+
+        let (started_tx, started_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        let writer = {
+            let list = Arc::clone(&list);
+            thread::spawn(move || {
+                list.write(|list| {
+                    list.insert(1, 1);
+                    started_tx.send(()).unwrap();
+                    // hold the write open until the reader has made progress
+                    done_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+                })
+            })
+        };
+
+        started_rx.recv().unwrap();
+
+        for _ in 0..1000 {
+            let snapshot = list.snapshot();
+            assert_eq!(snapshot.version(), 1);
+            assert_eq!(snapshot.values_of(&1).len(), 0);
+        }
+
+        done_tx.send(()).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(list.snapshot().values_of(&1), &[1]);
+    }
+
+    #[test]
+    fn stress_readers_see_consistent_versions() {
+        let list = Arc::new(ConcurrentSortedList::new());
+
+        let writers = (0..4u32)
+            .map(|w| {
+                let list = Arc::clone(&list);
+                thread::spawn(move || {
+                    for batch in 0..50u32 {
+                        list.extend((0..10).map(|i| (batch * 10 + i, w)));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let readers = (0..4)
+            .map(|_| {
+                let list = Arc::clone(&list);
+                thread::spawn(move || {
+                    let mut last_version = 0;
+                    for _ in 0..500 {
+                        let snapshot = list.snapshot();
+                        assert!(snapshot.version() >= last_version);
+                        last_version = snapshot.version();
+
+                        // every published version holds whole batches only
+                        assert_eq!(snapshot.len() as u64, snapshot.version() * 10);
+
+                        let keys = snapshot.keys().collect::<Vec<_>>();
+                        assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(list.version(), 200);
+        assert_eq!(list.snapshot().len(), 2000);
+    }
+}
@@ -0,0 +1,451 @@
+//! Cursors for walking back and forth over a `SortedList`.
+
+use std::fmt;
+
+use super::SortedList;
+
+/// A cursor over the tuples of a `SortedList`.
+///
+/// The cursor points either at a `(K, V)` tuple or at the "ghost" position which sits between the
+/// last and the first tuple. Moving past either end of the list lands on the ghost position, and
+/// moving once more from the ghost wraps around to the other end.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::SortedList;
+///
+/// let list = vec![(1u32, 'a'), (3, 'b'), (3, 'c'), (5, 'd')]
+///     .into_iter()
+///     .collect::<SortedList<_, _>>();
+///
+/// let mut cursor = list.cursor();
+/// assert!(cursor.seek_to(&3));
+/// assert_eq!(cursor.value(), Some(&'b'));
+/// assert_eq!(cursor.peek_prev(), Some((&1, &'a')));
+///
+/// cursor.move_next();
+/// cursor.move_next();
+/// assert_eq!(cursor.key(), Some(&5));
+/// ```
+pub struct Cursor<'a, K: Ord + 'a, V: PartialEq + 'a> {
+    list: &'a SortedList<K, V>,
+    index: usize,
+}
+
+impl<'a, K: Ord, V: PartialEq> Cursor<'a, K, V> {
+    pub(crate) fn new(list: &'a SortedList<K, V>, index: usize) -> Self {
+        Cursor { list, index }
+    }
+
+    /// Returns the index of the current tuple, or `None` if at the ghost position
+    pub fn index(&self) -> Option<usize> {
+        if self.index < self.list.len() {
+            Some(self.index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key of the current tuple
+    pub fn key(&self) -> Option<&'a K> {
+        self.list.keys.get(self.index)
+    }
+
+    /// Returns the value of the current tuple
+    pub fn value(&self) -> Option<&'a V> {
+        self.list.values.get(self.index)
+    }
+
+    /// Returns the current tuple
+    pub fn current(&self) -> Option<(&'a K, &'a V)> {
+        self.list.tuple_at(self.index)
+    }
+
+    /// Moves to the next tuple, or to the ghost position after the last one
+    pub fn move_next(&mut self) {
+        self.index = next_index(self.index, self.list.len());
+    }
+
+    /// Moves to the previous tuple, or to the ghost position before the first one
+    pub fn move_prev(&mut self) {
+        self.index = prev_index(self.index, self.list.len());
+    }
+
+    /// Returns the tuple `move_next` would move to
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.list.tuple_at(next_index(self.index, self.list.len()))
+    }
+
+    /// Returns the tuple `move_prev` would move to
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.list.tuple_at(prev_index(self.index, self.list.len()))
+    }
+
+    /// Moves to the first tuple
+    pub fn seek_first(&mut self) {
+        self.index = 0;
+    }
+
+    /// Moves to the last tuple
+    pub fn seek_last(&mut self) {
+        self.index = prev_index(self.list.len(), self.list.len());
+    }
+
+    /// Moves to the first tuple with a key equal to or greater than `key`, or to the ghost
+    /// position if there is no such tuple. Returns `true` if `key` was found.
+    pub fn seek_to(&mut self, key: &K) -> bool {
+        let (index, found) = self.list.seek_position(key);
+        self.index = index;
+        found
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq> Clone for Cursor<'a, K, V> {
+    fn clone(&self) -> Self {
+        Cursor {
+            list: self.list,
+            index: self.index,
+        }
+    }
+}
+
+impl<'a, K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for Cursor<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Cursor {{ {:?} }}", self.current())
+    }
+}
+
+/// A cursor over the tuples of a `SortedList` which can also remove and insert tuples at the
+/// current position.
+///
+/// The position of the cursor is kept up to date over its own modifications, unlike plain indices
+/// into the list.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::SortedList;
+///
+/// let mut list = vec![(1u32, 'a'), (3, 'b'), (5, 'c')]
+///     .into_iter()
+///     .collect::<SortedList<_, _>>();
+///
+/// {
+///     let mut cursor = list.cursor_mut();
+///     cursor.seek_to(&3);
+///     assert_eq!(cursor.remove_current(), Some((3, 'b')));
+///     assert_eq!(cursor.key(), Some(&5));
+///
+///     cursor.move_prev();
+///     assert!(cursor.insert_after(2, 'd').is_ok());
+///     // 6 would not be in order between 1 and 2
+///     assert_eq!(cursor.insert_after(6, 'e'), Err((6, 'e')));
+/// }
+///
+/// assert_eq!(
+///     list.iter().collect::<Vec<_>>(),
+///     vec![(&1, &'a'), (&2, &'d'), (&5, &'c')]);
+/// ```
+pub struct CursorMut<'a, K: Ord + 'a, V: PartialEq + 'a> {
+    list: &'a mut SortedList<K, V>,
+    index: usize,
+}
+
+impl<'a, K: Ord, V: PartialEq> CursorMut<'a, K, V> {
+    pub(crate) fn new(list: &'a mut SortedList<K, V>, index: usize) -> Self {
+        CursorMut { list, index }
+    }
+
+    /// Returns the index of the current tuple, or `None` if at the ghost position
+    pub fn index(&self) -> Option<usize> {
+        if self.index < self.list.len() {
+            Some(self.index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key of the current tuple
+    pub fn key(&self) -> Option<&K> {
+        self.list.keys.get(self.index)
+    }
+
+    /// Returns the value of the current tuple
+    pub fn value(&self) -> Option<&V> {
+        self.list.values.get(self.index)
+    }
+
+    /// Returns the current tuple
+    pub fn current(&self) -> Option<(&K, &V)> {
+        self.list.tuple_at(self.index)
+    }
+
+    /// Moves to the next tuple, or to the ghost position after the last one
+    pub fn move_next(&mut self) {
+        self.index = next_index(self.index, self.list.len());
+    }
+
+    /// Moves to the previous tuple, or to the ghost position before the first one
+    pub fn move_prev(&mut self) {
+        self.index = prev_index(self.index, self.list.len());
+    }
+
+    /// Returns the tuple `move_next` would move to
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.list.tuple_at(next_index(self.index, self.list.len()))
+    }
+
+    /// Returns the tuple `move_prev` would move to
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.list.tuple_at(prev_index(self.index, self.list.len()))
+    }
+
+    /// Moves to the first tuple
+    pub fn seek_first(&mut self) {
+        self.index = 0;
+    }
+
+    /// Moves to the last tuple
+    pub fn seek_last(&mut self) {
+        self.index = prev_index(self.list.len(), self.list.len());
+    }
+
+    /// Moves to the first tuple with a key equal to or greater than `key`, or to the ghost
+    /// position if there is no such tuple. Returns `true` if `key` was found.
+    pub fn seek_to(&mut self, key: &K) -> bool {
+        let (index, found) = self.list.seek_position(key);
+        self.index = index;
+        found
+    }
+
+    /// Returns a read-only cursor at the current position
+    pub fn as_cursor(&self) -> Cursor<'_, K, V> {
+        Cursor::new(self.list, self.index)
+    }
+
+    /// Removes and returns the current tuple, moving the cursor to the next one. Returns `None`
+    /// and does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.index < self.list.len() {
+            Some(self.list.remove_at(self.index))
+        } else {
+            None
+        }
+    }
+
+    /// Inserts the tuple right after the current one, or as the first tuple when at the ghost
+    /// position. The cursor stays at the current tuple.
+    ///
+    /// Returns the tuple back as `Err` if it would not be in order with its neighbours or if the
+    /// same `(key, value)` already exists in the list. With `ValueOrder::Sorted` the value must
+    /// also be in order with the neighbouring values of the same key. Otherwise it must become the
+    /// last value of its key, or the first with `ValueOrder::MostRecentFirst`, like the values
+    /// added by `insert`.
+    pub fn insert_after(&mut self, key: K, value: V) -> Result<(), (K, V)> {
+        let len = self.list.len();
+        let at = if self.index < len { self.index + 1 } else { 0 };
+
+        if !self.list.fits_before(at, &key, &value) || self.list.values_of(&key).contains(&value) {
+            return Err((key, value));
+        }
+
+        self.list.insert_at(at, key, value);
+
+        if self.index == len {
+            // stay at the ghost position
+            self.index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for CursorMut<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "CursorMut {{ {:?} }}", self.current())
+    }
+}
+
+fn next_index(index: usize, len: usize) -> usize {
+    if index >= len {
+        0
+    } else {
+        index + 1
+    }
+}
+
+fn prev_index(index: usize, len: usize) -> usize {
+    if index == 0 {
+        len
+    } else if index > len {
+        len.saturating_sub(1)
+    } else {
+        index - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+
+    fn sample() -> SortedList<u32, u8> {
+        vec![(0, 0), (0, 1), (1, 2), (3, 3), (3, 4), (5, 5)]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn move_around_and_wrap() {
+        let list = sample();
+        let mut cursor = list.cursor();
+
+        assert_eq!(cursor.current(), Some((&0, &0)));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.peek_prev(), Some((&5, &5)));
+        assert_eq!(cursor.peek_next(), Some((&0, &0)));
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some((&5, &5)));
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some((&0, &0)));
+    }
+
+    #[test]
+    fn seek() {
+        let list = sample();
+        let mut cursor = list.cursor();
+
+        assert!(cursor.seek_to(&3));
+        assert_eq!(cursor.index(), Some(3));
+        assert_eq!(cursor.value(), Some(&3));
+
+        assert!(!cursor.seek_to(&2));
+        assert_eq!(cursor.index(), Some(3));
+
+        assert!(!cursor.seek_to(&6));
+        assert_eq!(cursor.current(), None);
+
+        cursor.seek_last();
+        assert_eq!(cursor.current(), Some((&5, &5)));
+        cursor.seek_first();
+        assert_eq!(cursor.current(), Some((&0, &0)));
+    }
+
+    #[test]
+    fn empty_list() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        {
+            let mut cursor = list.cursor();
+            cursor.seek_last();
+            assert_eq!(cursor.current(), None);
+            cursor.move_prev();
+            assert_eq!(cursor.current(), None);
+            assert_eq!(cursor.peek_next(), None);
+        }
+
+        let mut cursor = list.cursor_mut();
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(cursor.insert_after(1, 1), Ok(()));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some((&1, &1)));
+    }
+
+    #[test]
+    fn remove_while_iterating() {
+        let mut list = sample();
+        {
+            let mut cursor = list.cursor_mut();
+            while let Some(key) = cursor.key().cloned() {
+                if key == 3 {
+                    cursor.remove_current();
+                } else {
+                    cursor.move_next();
+                }
+            }
+        }
+
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            vec![(&0, &0), (&0, &1), (&1, &2), (&5, &5)]
+        );
+    }
+
+    #[test]
+    fn insert_after_checks_order_and_duplicates() {
+        let mut list = sample();
+        {
+            let mut cursor = list.cursor_mut();
+            cursor.seek_to(&1);
+
+            assert_eq!(cursor.insert_after(0, 9), Err((0, 9)));
+            assert_eq!(cursor.insert_after(4, 9), Err((4, 9)));
+            assert_eq!(cursor.insert_after(3, 4), Err((3, 4)));
+            // would come before the values inserted earlier
+            assert_eq!(cursor.insert_after(3, 9), Err((3, 9)));
+
+            assert_eq!(cursor.insert_after(2, 9), Ok(()));
+            assert_eq!(cursor.current(), Some((&1, &2)));
+            assert_eq!(cursor.peek_next(), Some((&2, &9)));
+
+            cursor.seek_to(&3);
+            assert_eq!(cursor.insert_after(3, 9), Err((3, 9)));
+            cursor.move_next();
+            assert_eq!(cursor.insert_after(3, 9), Ok(()));
+            assert_eq!(cursor.peek_next(), Some((&3, &9)));
+
+            cursor.seek_last();
+            assert_eq!(cursor.insert_after(7, 7), Ok(()));
+        }
+
+        assert_eq!(list.values_of(&2), &[9]);
+        assert_eq!(list.values_of(&3), &[3, 4, 9]);
+        assert_eq!(list.last_value_of(&7), Some(&7));
+    }
+
+    #[test]
+    fn insert_after_keeps_sorted_values() {
+        let mut list: SortedList<u32, u8> = SortedList::sorted_values();
+        list.insert(0, 2);
+        list.insert(0, 4);
+
+        let mut cursor = list.cursor_mut();
+        assert_eq!(cursor.insert_after(0, 5), Err((0, 5)));
+        assert_eq!(cursor.insert_after(0, 1), Err((0, 1)));
+        assert_eq!(cursor.insert_after(0, 3), Ok(()));
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.insert_after(0, 3), Err((0, 3)));
+        assert_eq!(cursor.insert_after(0, 5), Ok(()));
+        assert_eq!(cursor.insert_after(1, 0), Err((1, 0)));
+    }
+
+    #[test]
+    fn insert_after_keeps_most_recent_first() {
+        let mut list: SortedList<u32, u8> = SortedList::most_recent_first();
+        list.insert(0, 1);
+        list.insert(0, 2);
+        list.insert(1, 3);
+
+        {
+            let mut cursor = list.cursor_mut();
+            assert_eq!(cursor.insert_after(0, 4), Err((0, 4)));
+
+            cursor.move_next();
+            assert_eq!(cursor.insert_after(0, 4), Err((0, 4)));
+            assert_eq!(cursor.insert_after(1, 4), Ok(()));
+
+            cursor.seek_last();
+            cursor.move_next();
+            assert_eq!(cursor.insert_after(0, 5), Ok(()));
+        }
+
+        assert_eq!(list.values_of(&0), &[5, 2, 1]);
+        assert_eq!(list.values_of(&1), &[4, 3]);
+    }
+}
@@ -0,0 +1,345 @@
+//! Differences between two `SortedList`s and applying them as patches.
+
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::SortedList;
+
+/// A single change turning one `SortedList` into another, created by `SortedList::diff`.
+///
+/// The indices are positions in the list at the time the change is applied, so the changes must be
+/// applied in order. With the `serde` feature owned changes can be serialized and sent elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Change<K, V> {
+    /// Insert the tuple at `index`
+    Inserted {
+        /// Position to insert at
+        index: usize,
+        /// Key of the inserted tuple
+        key: K,
+        /// Value of the inserted tuple
+        value: V,
+    },
+    /// Remove the tuple at `index`
+    Removed {
+        /// Position to remove from
+        index: usize,
+        /// Key of the removed tuple
+        key: K,
+        /// Value of the removed tuple
+        value: V,
+    },
+}
+
+impl<'a, K: Clone, V: Clone> Change<&'a K, &'a V> {
+    /// Clones the key and value, so that the change can be stored or sent
+    pub fn cloned(&self) -> Change<K, V> {
+        match *self {
+            Change::Inserted { index, key, value } => Change::Inserted {
+                index,
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Change::Removed { index, key, value } => Change::Removed {
+                index,
+                key: key.clone(),
+                value: value.clone(),
+            },
+        }
+    }
+}
+
+/// Error returned by `SortedList::apply` when a change does not fit the list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchError {
+    change: usize,
+}
+
+impl PatchError {
+    /// Returns the position of the failed change in the patch
+    pub fn change(&self) -> usize {
+        self.change
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "change #{} does not apply to the list, the patch was made against another version",
+            self.change
+        )
+    }
+}
+
+impl Error for PatchError {}
+
+impl<K: Ord, V: PartialEq> SortedList<K, V> {
+    /// Returns the changes which turn this list into `other`, in the order they need to be applied.
+    ///
+    /// Computed in a single pass over both lists. For a key found in both lists the values are
+    /// kept up to the first difference, after which the rest of the values of the key are
+    /// removed and the values of `other` inserted, which keeps the order of the values intact.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::{Change, SortedList};
+    ///
+    /// let before = vec![(1u32, 'a'), (2, 'b')].into_iter().collect::<SortedList<_, _>>();
+    /// let after = vec![(1u32, 'a'), (1, 'c'), (3, 'd')].into_iter().collect::<SortedList<_, _>>();
+    ///
+    /// let patch = before.diff(&after).map(|change| change.cloned()).collect::<Vec<_>>();
+    /// assert_eq!(patch[0], Change::Inserted { index: 1, key: 1, value: 'c' });
+    ///
+    /// let mut list = before.clone();
+    /// list.apply(patch).unwrap();
+    /// assert_eq!(list.iter().collect::<Vec<_>>(), after.iter().collect::<Vec<_>>());
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a SortedList<K, V>) -> Diff<'a, K, V> {
+        Diff {
+            from: self,
+            to: other,
+            i: 0,
+            j: 0,
+            index: 0,
+            remove_until: 0,
+            insert_until: 0,
+        }
+    }
+
+    /// Applies the changes created by `diff`. Nothing is changed if any of the changes does not
+    /// fit, for example because the patch was made against another version of the list.
+    pub fn apply<I>(&mut self, patch: I) -> Result<(), PatchError>
+    where
+        I: IntoIterator<Item = Change<K, V>>,
+    {
+        self.transaction(|tx| {
+            for (n, change) in patch.into_iter().enumerate() {
+                let applied = match change {
+                    Change::Inserted { index, key, value } => tx.insert_at(index, key, value),
+                    Change::Removed { index, key, value } => tx.remove_at(index, &key, &value),
+                };
+
+                if !applied {
+                    return Err(PatchError { change: n });
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Iterator over the changes between two `SortedList`s, created by `SortedList::diff`
+pub struct Diff<'a, K: Ord + 'a, V: PartialEq + 'a> {
+    from: &'a SortedList<K, V>,
+    to: &'a SortedList<K, V>,
+    /// next tuple of `from`
+    i: usize,
+    /// next tuple of `to`
+    j: usize,
+    /// position in the list being patched
+    index: usize,
+    remove_until: usize,
+    insert_until: usize,
+}
+
+impl<'a, K: Ord, V: PartialEq> Iterator for Diff<'a, K, V> {
+    type Item = Change<&'a K, &'a V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.i < self.remove_until {
+                let (key, value) = self.from.tuple_at(self.i).unwrap();
+                self.i += 1;
+                return Some(Change::Removed {
+                    index: self.index,
+                    key,
+                    value,
+                });
+            }
+
+            if self.j < self.insert_until {
+                let (key, value) = self.to.tuple_at(self.j).unwrap();
+                self.j += 1;
+                self.index += 1;
+                return Some(Change::Inserted {
+                    index: self.index - 1,
+                    key,
+                    value,
+                });
+            }
+
+            let from_keys = self.from.key_slice();
+            let to_keys = self.to.key_slice();
+
+            match (from_keys.get(self.i), to_keys.get(self.j)) {
+                (None, None) => return None,
+                (Some(_), None) => self.remove_until = from_keys.len(),
+                (None, Some(_)) => self.insert_until = to_keys.len(),
+                (Some(a), Some(b)) if a < b => self.remove_until = group_end(from_keys, self.i),
+                (Some(a), Some(b)) if a > b => self.insert_until = group_end(to_keys, self.j),
+                (Some(_), Some(_)) => {
+                    let from_end = group_end(from_keys, self.i);
+                    let to_end = group_end(to_keys, self.j);
+
+                    let from_values = self.from.value_slice();
+                    let to_values = self.to.value_slice();
+
+                    while self.i < from_end
+                        && self.j < to_end
+                        && from_values[self.i] == to_values[self.j]
+                    {
+                        self.i += 1;
+                        self.j += 1;
+                        self.index += 1;
+                    }
+
+                    self.remove_until = from_end;
+                    self.insert_until = to_end;
+                }
+            }
+        }
+    }
+}
+
+fn group_end<K: Ord>(keys: &[K], from: usize) -> usize {
+    let key = &keys[from];
+    from + keys[from..].partition_point(|k| k == key)
+}
+
+impl<'a, K: Ord, V: PartialEq> fmt::Debug for Diff<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Diff {{ from: {}, to: {} }}", self.i, self.j)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::Change;
+
+    fn list(tuples: &[(u32, u8)]) -> SortedList<u32, u8> {
+        tuples.iter().cloned().collect()
+    }
+
+    fn round_trip(from: &SortedList<u32, u8>, to: &SortedList<u32, u8>) -> usize {
+        let patch = from.diff(to).map(|c| c.cloned()).collect::<Vec<_>>();
+        let len = patch.len();
+
+        let mut patched = from.clone();
+        patched.apply(patch).unwrap();
+        assert_eq!(
+            patched.iter().collect::<Vec<_>>(),
+            to.iter().collect::<Vec<_>>()
+        );
+        len
+    }
+
+    #[test]
+    fn identical_lists_have_no_changes() {
+        let a = list(&[(0, 0), (0, 1), (2, 2)]);
+        assert_eq!(a.diff(&a.clone()).count(), 0);
+    }
+
+    #[test]
+    fn round_trips() {
+        let a = list(&[(0, 0), (0, 1), (1, 5), (2, 2), (4, 4)]);
+        let b = list(&[(0, 0), (0, 2), (0, 1), (2, 2), (3, 3), (5, 5)]);
+
+        assert_eq!(round_trip(&a, &b), 7);
+        round_trip(&b, &a);
+        round_trip(&a, &SortedList::new());
+        round_trip(&SortedList::new(), &b);
+    }
+
+    #[test]
+    fn keeps_order_of_values() {
+        let a = list(&[(0, 1), (0, 2), (0, 3)]);
+        let b = list(&[(0, 1), (0, 3), (0, 2)]);
+
+        assert_eq!(
+            a.diff(&b).collect::<Vec<_>>(),
+            vec![
+                Change::Removed {
+                    index: 1,
+                    key: &0,
+                    value: &2
+                },
+                Change::Removed {
+                    index: 1,
+                    key: &0,
+                    value: &3
+                },
+                Change::Inserted {
+                    index: 1,
+                    key: &0,
+                    value: &3
+                },
+                Change::Inserted {
+                    index: 2,
+                    key: &0,
+                    value: &2
+                },
+            ]
+        );
+        round_trip(&a, &b);
+    }
+
+    #[test]
+    fn most_recent_first_round_trip() {
+        let mut a = SortedList::most_recent_first();
+        let mut b = SortedList::most_recent_first();
+        for v in 0..5u8 {
+            a.insert(1u32, v);
+            b.insert(1u32, 4 - v);
+        }
+        b.insert(0, 0);
+
+        round_trip(&a, &b);
+    }
+
+    #[test]
+    fn stale_patch_changes_nothing() {
+        let a = list(&[(0, 0), (1, 1)]);
+        let b = list(&[(0, 0), (2, 2)]);
+        let patch = a.diff(&b).map(|c| c.cloned()).collect::<Vec<_>>();
+
+        let mut other = list(&[(0, 0), (1, 2)]);
+        let err = other.apply(patch).unwrap_err();
+        assert_eq!(err.change(), 0);
+        assert_eq!(other.iter().collect::<Vec<_>>(), vec![(&0, &0), (&1, &2)]);
+
+        let mut other = list(&[(0, 0)]);
+        let bad = vec![Change::Inserted {
+            index: 0,
+            key: 1,
+            value: 1,
+        }];
+        assert!(other.apply(bad).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_patch() {
+        use serde_json;
+
+        let a = list(&[(0, 0), (1, 1)]);
+        let b = list(&[(0, 0), (2, 2)]);
+        let patch = a.diff(&b).map(|c| c.cloned()).collect::<Vec<_>>();
+
+        let json = serde_json::to_string(&patch).unwrap();
+        let patch: Vec<Change<u32, u8>> = serde_json::from_str(&json).unwrap();
+
+        let mut patched = a.clone();
+        patched.apply(patch).unwrap();
+        assert_eq!(
+            patched.iter().collect::<Vec<_>>(),
+            b.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Bloom filter over the keys of a `SortedList` for fast negative lookups.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::SortedList;
+
+/// Bits per key of the filters built by `SortedList::build_filter`, for about 1% false positives
+const DEFAULT_BITS_PER_KEY: usize = 10;
+
+/// Bloom filter over keys, answering whether a key may be in a list or is surely not in it.
+///
+/// Created by `SortedList::build_filter` or kept up to date by a `FilteredSortedList`. With the
+/// `serde` feature the filter can be stored next to the list it was built from. The keys are
+/// hashed with FNV-1a over their `Hash` impl, feeding integers as little-endian bytes and `usize`
+/// and `isize` as 64 bit integers, so a stored filter stays valid between runs and platforms as
+/// long as the `Hash` impl of the key type stays the same. A filter without any bits, such as a
+/// mangled stored one, may contain every key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    bits_per_key: usize,
+    capacity: usize,
+    keys: usize,
+}
+
+impl KeyFilter {
+    /// Creates an empty filter sized for `capacity` keys with `bits_per_key` bits each. More bits
+    /// per key means fewer false positives: 10 bits give about 1%, 15 bits about 0.1%.
+    pub fn with_capacity(capacity: usize, bits_per_key: usize) -> Self {
+        let bits_per_key = bits_per_key.max(1);
+        let words = (capacity.max(1) * bits_per_key + 63) / 64;
+        // ln 2 * bits per key hashes minimize the false positives
+        let hashes = ((bits_per_key as f64) * ::std::f64::consts::LN_2).round() as u32;
+
+        KeyFilter {
+            bits: vec![0; words],
+            hashes: hashes.clamp(1, 30),
+            bits_per_key,
+            capacity,
+            keys: 0,
+        }
+    }
+
+    /// Adds `key` to the filter
+    pub fn insert<K: Hash + ?Sized>(&mut self, key: &K) {
+        let len = self.bits.len() as u64 * 64;
+        for bit in self.bits_of(key).take_while(|_| len > 0) {
+            let bit = bit % len;
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.keys += 1;
+    }
+
+    /// Returns `false` if `key` was surely never added, or `true` if it may have been
+    pub fn may_contain<K: Hash + ?Sized>(&self, key: &K) -> bool {
+        let len = self.bits.len() as u64 * 64;
+        len == 0
+            || self.bits_of(key).all(|bit| {
+                let bit = bit % len;
+                self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+            })
+    }
+
+    /// Returns the number of keys added
+    pub fn len(&self) -> usize {
+        self.keys
+    }
+
+    /// Returns `true` if no key was added
+    pub fn is_empty(&self) -> bool {
+        self.keys == 0
+    }
+
+    /// Returns the number of keys the filter was sized for
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of bits per key the filter was sized with
+    pub fn bits_per_key(&self) -> usize {
+        self.bits_per_key
+    }
+
+    /// Returns the bits to set for `key`, using double hashing to derive all of them from one
+    /// 64 bit hash
+    fn bits_of<K: Hash + ?Sized>(&self, key: &K) -> impl Iterator<Item = u64> {
+        let mut hasher = Fnv(FNV_OFFSET);
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let first = hash;
+        let step = hash.rotate_right(32) | 1;
+        (0..u64::from(self.hashes)).map(move |i| first.wrapping_add(i.wrapping_mul(step)))
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// FNV-1a, which unlike the `DefaultHasher` is the same in every Rust release. Integers are fed
+/// in the same byte order and width on every platform.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl<K: Ord + Hash, V: PartialEq> SortedList<K, V> {
+    /// Builds a `KeyFilter` over the distinct keys of the list, sized for exactly those keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let list = (0..1000u32).map(|i| (i * 2, i)).collect::<SortedList<_, _>>();
+    /// let filter = list.build_filter();
+    ///
+    /// assert!(filter.may_contain(&10));
+    /// assert!((0..1000u32).filter(|i| filter.may_contain(&(i * 2 + 1))).count() < 50);
+    /// ```
+    pub fn build_filter(&self) -> KeyFilter {
+        self.filter_with_capacity(self.distinct_keys(), DEFAULT_BITS_PER_KEY)
+    }
+
+    fn filter_with_capacity(&self, capacity: usize, bits_per_key: usize) -> KeyFilter {
+        let mut filter = KeyFilter::with_capacity(capacity, bits_per_key);
+        let keys = self.key_slice();
+        for (i, key) in keys.iter().enumerate() {
+            if i == 0 || keys[i - 1] != *key {
+                filter.insert(key);
+            }
+        }
+        filter
+    }
+
+    fn distinct_keys(&self) -> usize {
+        let keys = self.key_slice();
+        keys.iter()
+            .enumerate()
+            .filter(|&(i, key)| i == 0 || keys[i - 1] != *key)
+            .count()
+    }
+}
+
+/// `SortedList` with a `KeyFilter` attached, so that looking up absent keys usually returns
+/// without a binary search.
+///
+/// The filter is updated on every insert and rebuilt with twice the room once it is full. Keys
+/// whose last tuple was removed stay in the filter until it is rebuilt, which only makes their
+/// lookups take the binary search. All of the read-only methods of `SortedList` are available
+/// through `Deref`.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::FilteredSortedList;
+///
+/// let mut list = FilteredSortedList::new();
+/// list.insert("b", 1);
+/// list.insert("d", 2);
+///
+/// assert!(list.contains_key(&"b"));
+/// assert!(!list.contains_key(&"c"));
+/// assert_eq!(list.values_of(&"d"), &[2]);
+///
+/// // store the filter next to the list, and attach it again when loading both
+/// let (list, filter) = list.into_parts();
+/// let list: FilteredSortedList<_, _> = FilteredSortedList::with_filter(list, filter);
+/// assert!(list.contains_key(&"d"));
+/// ```
+pub struct FilteredSortedList<K: Ord, V: PartialEq> {
+    list: SortedList<K, V>,
+    filter: KeyFilter,
+}
+
+impl<K: Ord + Hash, V: PartialEq> FilteredSortedList<K, V> {
+    /// Creates a new empty list
+    pub fn new() -> Self {
+        Self::from(SortedList::new())
+    }
+
+    /// Attaches a filter built from `list`, for example one stored with it. A filter built from
+    /// another list makes lookups miss keys.
+    pub fn with_filter(list: SortedList<K, V>, filter: KeyFilter) -> Self {
+        FilteredSortedList { list, filter }
+    }
+
+    /// Inserts the tuple, see `SortedList::insert`
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        let index = match self.list.insert_index(key, value) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let (key, _) = self.list.tuple_at(index).unwrap();
+        let new_key = self
+            .list
+            .tuple_at(index.wrapping_sub(1))
+            .map_or(true, |(k, _)| k != key)
+            && self
+                .list
+                .tuple_at(index + 1)
+                .map_or(true, |(k, _)| k != key);
+
+        if new_key {
+            if self.filter.len() < self.filter.capacity() {
+                self.filter.insert(key);
+            } else {
+                self.rebuild_filter();
+            }
+        }
+        true
+    }
+
+    /// Removes the tuple, see `SortedList::remove`
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        self.list.remove(key, value)
+    }
+
+    /// Removes and returns the first tuple, see `SortedList::pop_first`
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.list.pop_first()
+    }
+
+    /// Removes and returns the last tuple, see `SortedList::pop_last`
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.list.pop_last()
+    }
+
+    /// Removes all tuples and empties the filter
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.filter = KeyFilter::with_capacity(self.filter.capacity(), self.filter.bits_per_key());
+    }
+
+    /// Returns `true` if there is at least one tuple with `key`, checking the filter first
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.filter.may_contain(key) && self.list.contains_key(key)
+    }
+
+    /// Returns the values of `key`, checking the filter first, see `SortedList::values_of`
+    pub fn values_of(&self, key: &K) -> &[V] {
+        if self.filter.may_contain(key) {
+            self.list.values_of(key)
+        } else {
+            &self.list.value_slice()[0..0]
+        }
+    }
+
+    /// Returns the filter, for example to store it next to the list
+    pub fn filter(&self) -> &KeyFilter {
+        &self.filter
+    }
+
+    /// Rebuilds the filter from the current keys with room for as many again, dropping the keys
+    /// which were removed
+    pub fn rebuild_filter(&mut self) {
+        let capacity = self.list.distinct_keys() * 2;
+        self.filter = self
+            .list
+            .filter_with_capacity(capacity, self.filter.bits_per_key());
+    }
+
+    /// Returns the list and the filter
+    pub fn into_parts(self) -> (SortedList<K, V>, KeyFilter) {
+        (self.list, self.filter)
+    }
+
+    /// Returns the list, dropping the filter
+    pub fn into_inner(self) -> SortedList<K, V> {
+        self.list
+    }
+}
+
+impl<K: Ord + Hash, V: PartialEq> Default for FilteredSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Hash, V: PartialEq> From<SortedList<K, V>> for FilteredSortedList<K, V> {
+    fn from(list: SortedList<K, V>) -> Self {
+        let capacity = list.distinct_keys() * 2;
+        let filter = list.filter_with_capacity(capacity, DEFAULT_BITS_PER_KEY);
+        FilteredSortedList { list, filter }
+    }
+}
+
+impl<K: Ord, V: PartialEq> Deref for FilteredSortedList<K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for FilteredSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "FilteredSortedList {{ {:?} }}", self.list.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::{FilteredSortedList, Fnv, KeyFilter, FNV_OFFSET};
+    use std::hash::{Hash, Hasher};
+
+    #[test]
+    fn no_false_negatives() {
+        let mut filter = KeyFilter::with_capacity(1000, 10);
+        for i in 0..1000u32 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000u32).all(|i| filter.may_contain(&i)));
+
+        let false_positives = (1000..11000u32).filter(|i| filter.may_contain(i)).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn follows_changes() {
+        let mut list = FilteredSortedList::new();
+        for i in 0..500u32 {
+            list.insert(i % 200, i);
+        }
+        assert_eq!(list.filter().len(), 200);
+
+        for i in 0..50u32 {
+            assert!(list.remove(&i, &i));
+            assert!(list.remove(&i, &(i + 200)));
+        }
+        list.pop_last();
+
+        for key in 0..300 {
+            assert_eq!(list.contains_key(&key), list.list.contains_key(&key));
+            assert_eq!(list.values_of(&key), list.list.values_of(&key));
+        }
+
+        list.rebuild_filter();
+        assert_eq!(list.filter().len(), 200);
+
+        list.clear();
+        assert!(list.filter().is_empty());
+        assert!(!list.contains_key(&100));
+    }
+
+    #[test]
+    fn build_filter() {
+        let list = vec![(1u32, 'a'), (1, 'b'), (3, 'c')]
+            .into_iter()
+            .collect::<SortedList<_, _>>();
+        let filter = list.build_filter();
+
+        assert_eq!(filter.len(), 2);
+        assert!(filter.may_contain(&1) && filter.may_contain(&3));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_filter() {
+        use serde_json;
+
+        let list = (0..100u32).map(|i| (i, i)).collect::<SortedList<_, _>>();
+        let json = serde_json::to_string(&list.build_filter()).unwrap();
+        let filter: KeyFilter = serde_json::from_str(&json).unwrap();
+
+        let list = FilteredSortedList::with_filter(list, filter);
+        assert!((0..100u32).all(|i| list.contains_key(&i)));
+    }
+
+    #[test]
+    fn same_hashes_on_every_platform() {
+        fn hash<K: Hash>(key: K) -> u64 {
+            let mut hasher = Fnv(FNV_OFFSET);
+            key.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut bytes = Fnv(FNV_OFFSET);
+        bytes.write(&[4, 3, 2, 1]);
+        assert_eq!(hash(0x0102_0304u32), bytes.finish());
+        assert_eq!(hash(7usize), hash(7u64));
+        assert_eq!(hash(-7isize), hash(-7i64));
+        assert_eq!(hash(&[1u8, 2][..]), hash((2u64, 1u8, 2u8)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialized_filter_without_bits() {
+        use serde_json;
+
+        let json = r#"{"bits":[],"hashes":7,"bits_per_key":10,"capacity":0,"keys":0}"#;
+        let mut filter: KeyFilter = serde_json::from_str(json).unwrap();
+
+        assert!(filter.may_contain(&1u32));
+        filter.insert(&1u32);
+        assert!(filter.may_contain(&2u32));
+    }
+}
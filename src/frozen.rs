@@ -0,0 +1,304 @@
+//! Read-only `SortedList` with its keys laid out for fast searches.
+
+use std::fmt;
+use std::ops::{Bound, Deref, RangeBounds};
+
+use super::{SortedList, Tuples};
+
+/// Number of keys in a node of the layout
+const NODE: usize = 16;
+
+/// Read-only `SortedList` which searches a copy of its distinct keys laid out as a static B-tree,
+/// created by `SortedList::freeze`.
+///
+/// The tree has 16 keys in every node, which are next to each other in memory, so a search visits
+/// `log17(n)` nodes and counts the smaller keys in each of them instead of jumping around the whole
+/// list. This suits lists which are built once and then searched a lot. The positions of the
+/// values of every key are stored too, so `values_of` takes a single search.
+///
+/// The searching methods are replaced by faster ones and the rest of the read-only methods of
+/// `SortedList` are available through `Deref`. `thaw` returns the list for changing it again.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::SortedList;
+///
+/// let mut list = SortedList::new();
+/// list.insert(2u32, 'b');
+/// list.insert(1, 'a');
+/// list.insert(2, 'c');
+///
+/// let frozen = list.freeze();
+/// assert_eq!(frozen.values_of(&2), &['b', 'c']);
+/// assert_eq!(frozen.range(..2).count(), 1);
+/// assert_eq!(frozen.len(), 3);
+///
+/// let mut list = frozen.thaw();
+/// list.insert(3, 'd');
+/// ```
+pub struct FrozenSortedList<K: Ord, V: PartialEq> {
+    list: SortedList<K, V>,
+    // the distinct keys, and the range of the tuples of every key in the list in the same order,
+    // kept apart so that the searched keys are packed tightly
+    layout: Vec<K>,
+    ranges: Vec<(usize, usize)>,
+}
+
+impl<K: Ord + Clone, V: PartialEq> SortedList<K, V> {
+    /// Freezes the list into a `FrozenSortedList` which searches faster but cannot be changed.
+    /// Takes linear time and a copy of every distinct key.
+    pub fn freeze(self) -> FrozenSortedList<K, V> {
+        let mut groups = Vec::new();
+        let keys = self.key_slice();
+        let mut start = 0;
+        while start < keys.len() {
+            let end = start + keys[start..].partition_point(|k| *k == keys[start]);
+            groups.push((start, end));
+            start = end;
+        }
+
+        let ranges = node_positions(groups.len())
+            .into_iter()
+            .map(|group| groups[group])
+            .collect::<Vec<_>>();
+        let layout = ranges
+            .iter()
+            .map(|&(start, _)| keys[start].clone())
+            .collect();
+
+        FrozenSortedList {
+            list: self,
+            layout,
+            ranges,
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> FrozenSortedList<K, V> {
+    /// Returns the values of `key`, see `SortedList::values_of`
+    pub fn values_of(&self, key: &K) -> &[V] {
+        let values = self.list.value_slice();
+        match self.find(key) {
+            Some(node) => {
+                let (start, end) = self.ranges[node];
+                &values[start..end]
+            }
+            None => &values[0..0],
+        }
+    }
+
+    /// Returns `true` if there is at least one tuple with `key`
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Returns the first value of `key`, see `SortedList::first_value_of`
+    pub fn first_value_of(&self, key: &K) -> Option<&V> {
+        self.values_of(key).first()
+    }
+
+    /// Returns the last value of `key`, see `SortedList::last_value_of`
+    pub fn last_value_of(&self, key: &K) -> Option<&V> {
+        self.values_of(key).last()
+    }
+
+    /// Returns an iterator over the specified range of tuples, see `SortedList::range`
+    pub fn range<R>(&self, range: R) -> Tuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let low = match range.start_bound() {
+            Bound::Included(key) => self.position(|k| k < key),
+            Bound::Excluded(key) => self.position(|k| k <= key),
+            Bound::Unbounded => 0,
+        };
+
+        let high = match range.end_bound() {
+            Bound::Included(key) => self.position(|k| k <= key),
+            Bound::Excluded(key) => self.position(|k| k < key),
+            Bound::Unbounded => self.list.len(),
+        };
+
+        Tuples {
+            keys: self.list.key_slice(),
+            values: self.list.value_slice(),
+            low,
+            high: high.max(low),
+        }
+    }
+
+    /// Returns the list so that it can be changed again, dropping the copy of the keys
+    pub fn thaw(self) -> SortedList<K, V> {
+        self.list
+    }
+
+    /// Returns the slot of `key` in the layout
+    fn find(&self, key: &K) -> Option<usize> {
+        self.lower_bound(|k| k < key)
+            .filter(|&slot| self.layout[slot] == *key)
+    }
+
+    /// Returns the position of the first tuple whose key `less` returns `false` for
+    fn position<F>(&self, less: F) -> usize
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.lower_bound(less)
+            .map_or(self.list.len(), |slot| self.ranges[slot].0)
+    }
+
+    /// Returns the slot of the first key in the layout for which `less` returns `false`
+    fn lower_bound<F>(&self, mut less: F) -> Option<usize>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut node = 0;
+        let mut found = None;
+
+        while node * NODE < self.layout.len() {
+            let start = node * NODE;
+            let keys = &self.layout[start..self.layout.len().min(start + NODE)];
+
+            // the keys of a node are sorted, so the smaller ones are all before the rest
+            let smaller = keys.iter().filter(|k| less(k)).count();
+            if smaller < keys.len() {
+                found = Some(start + smaller);
+            }
+            node = node * (NODE + 1) + smaller + 1;
+        }
+
+        found
+    }
+}
+
+/// Returns the sorted position stored in every slot of a layout of `len` keys, where node `n`
+/// holds the slots `n * NODE..(n + 1) * NODE` and its children are the nodes
+/// `n * (NODE + 1) + 1..=n * (NODE + 1) + NODE + 1`, the child `i` holding the keys between the
+/// keys `i - 1` and `i` of the node
+fn node_positions(len: usize) -> Vec<usize> {
+    fn fill(positions: &mut [usize], node: usize, next: &mut usize) {
+        let start = node * NODE;
+        if start >= positions.len() {
+            return;
+        }
+
+        for i in 0..NODE {
+            fill(positions, node * (NODE + 1) + i + 1, next);
+            if start + i < positions.len() {
+                positions[start + i] = *next;
+                *next += 1;
+            }
+        }
+        fill(positions, node * (NODE + 1) + NODE + 1, next);
+    }
+
+    let mut positions = vec![0; len];
+    fill(&mut positions, 0, &mut 0);
+    positions
+}
+
+impl<K: Ord + Clone, V: PartialEq> From<SortedList<K, V>> for FrozenSortedList<K, V> {
+    fn from(list: SortedList<K, V>) -> Self {
+        list.freeze()
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq + Clone> Clone for FrozenSortedList<K, V> {
+    fn clone(&self) -> Self {
+        FrozenSortedList {
+            list: self.list.clone(),
+            layout: self.layout.clone(),
+            ranges: self.ranges.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> Deref for FrozenSortedList<K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for FrozenSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "FrozenSortedList {{ {:?} }}", self.list.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use std::ops::Bound;
+
+    fn list() -> SortedList<u32, u32> {
+        (0..1000u32).map(|i| ((i * 7919) % 600, i)).collect()
+    }
+
+    #[test]
+    fn searches_like_the_list() {
+        let list = list();
+        let frozen = list.clone().freeze();
+
+        for key in 0..650 {
+            assert_eq!(frozen.values_of(&key), list.values_of(&key));
+            assert_eq!(frozen.contains_key(&key), !list.values_of(&key).is_empty());
+            assert_eq!(frozen.first_value_of(&key), list.first_value_of(&key));
+            assert_eq!(frozen.last_value_of(&key), list.last_value_of(&key));
+        }
+    }
+
+    #[test]
+    fn ranges_like_the_list() {
+        let list = list();
+        let frozen = list.clone().freeze();
+
+        for &(low, high) in &[(0, 600), (10, 20), (599, 700), (30, 30), (40, 35)] {
+            let bounds = [
+                (Bound::Included(low), Bound::Excluded(high)),
+                (Bound::Excluded(low), Bound::Included(high)),
+                (Bound::Unbounded, Bound::Included(high)),
+                (Bound::Included(low), Bound::Unbounded),
+            ];
+            for &range in &bounds {
+                assert_eq!(
+                    frozen.range(range).collect::<Vec<_>>(),
+                    list.range(range).collect::<Vec<_>>(),
+                    "{:?}",
+                    range
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_size() {
+        for len in 0..400u32 {
+            let frozen = (0..len)
+                .map(|i| (i * 2, i))
+                .collect::<SortedList<_, _>>()
+                .freeze();
+
+            for key in 0..len * 2 + 2 {
+                assert_eq!(frozen.contains_key(&key), key % 2 == 0 && key < len * 2);
+                assert_eq!(
+                    frozen.range(key..).next().map(|(k, _)| *k),
+                    Some(key + key % 2).filter(|k| *k < len * 2)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_and_thaw() {
+        let frozen = SortedList::<u32, u32>::new().freeze();
+        assert!(frozen.values_of(&1).is_empty());
+        assert_eq!(frozen.range(..).count(), 0);
+
+        let mut list = list().freeze().thaw();
+        assert!(list.insert(1000, 1000));
+        assert_eq!(list.values_of(&1000), &[1000]);
+    }
+}
@@ -0,0 +1,304 @@
+//! `SortedList` with a reverse index from values to keys.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+
+use super::SortedList;
+
+/// Reverse index from values to the keys they are stored under, kept by an
+/// `IndexedSortedList`.
+pub trait ReverseIndex<K, V>: Default {
+    /// Records that `value` was inserted under `key`
+    fn insert(&mut self, key: &K, value: &V);
+
+    /// Records that `value` was removed from `key`
+    fn remove(&mut self, key: &K, value: &V);
+
+    /// Returns the keys `value` is stored under, in order
+    fn keys_of(&self, value: &V) -> &[K];
+
+    /// Forgets all of the values
+    fn clear(&mut self);
+}
+
+/// `ReverseIndex` for `V: Hash + Eq`, keeping a small sorted `Vec` of keys for every value
+#[derive(Debug, Clone)]
+pub struct HashIndex<K, V> {
+    keys: HashMap<V, Vec<K>>,
+}
+
+impl<K, V: Hash + Eq> Default for HashIndex<K, V> {
+    fn default() -> Self {
+        HashIndex {
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Hash + Eq + Clone> ReverseIndex<K, V> for HashIndex<K, V> {
+    fn insert(&mut self, key: &K, value: &V) {
+        let keys = self.keys.entry(value.clone()).or_default();
+        if let Err(at) = keys.binary_search(key) {
+            keys.insert(at, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K, value: &V) {
+        let now_empty = match self.keys.get_mut(value) {
+            Some(keys) => {
+                if let Ok(at) = keys.binary_search(key) {
+                    keys.remove(at);
+                }
+                keys.is_empty()
+            }
+            None => false,
+        };
+
+        if now_empty {
+            self.keys.remove(value);
+        }
+    }
+
+    fn keys_of(&self, value: &V) -> &[K] {
+        self.keys.get(value).map_or(&[], |keys| keys.as_slice())
+    }
+
+    fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+/// `ReverseIndex` for `V: Ord`, stored as a `SortedList` from values to sorted keys
+#[derive(Debug, Clone)]
+pub struct OrdIndex<K: Ord, V: Ord> {
+    keys: SortedList<V, K>,
+}
+
+impl<K: Ord, V: Ord> Default for OrdIndex<K, V> {
+    fn default() -> Self {
+        OrdIndex {
+            keys: SortedList::sorted_values(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Ord + Clone> ReverseIndex<K, V> for OrdIndex<K, V> {
+    fn insert(&mut self, key: &K, value: &V) {
+        self.keys.insert(value.clone(), key.clone());
+    }
+
+    fn remove(&mut self, key: &K, value: &V) {
+        self.keys.remove(value, key);
+    }
+
+    fn keys_of(&self, value: &V) -> &[K] {
+        self.keys.values_of(value)
+    }
+
+    fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+/// `SortedList` which also indexes the tuples by value, answering which keys a value is stored
+/// under without scanning the whole list.
+///
+/// The index is a `HashIndex` by default, or an `OrdIndex` for values which are `Ord` but not
+/// `Hash`. All of the read-only methods of `SortedList` are available through `Deref`.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::{IndexedSortedList, OrdIndex};
+///
+/// let mut list: IndexedSortedList<u32, &str> = IndexedSortedList::new();
+/// list.insert(3, "x");
+/// list.insert(1, "x");
+/// list.insert(1, "y");
+///
+/// assert_eq!(list.keys_of(&"x"), &[1, 3]);
+/// assert_eq!(list.positions_of(&"x"), vec![0, 2]);
+///
+/// let mut list: IndexedSortedList<u32, &str, OrdIndex<_, _>> = IndexedSortedList::new();
+/// list.insert(2, "z");
+/// assert_eq!(list.keys_of(&"z"), &[2]);
+/// ```
+pub struct IndexedSortedList<K: Ord, V: PartialEq, I = HashIndex<K, V>> {
+    list: SortedList<K, V>,
+    index: I,
+}
+
+impl<K: Ord, V: PartialEq, I: ReverseIndex<K, V>> IndexedSortedList<K, V, I> {
+    /// Creates a new empty list
+    pub fn new() -> Self {
+        IndexedSortedList {
+            list: SortedList::new(),
+            index: I::default(),
+        }
+    }
+
+    /// Inserts the tuple, see `SortedList::insert`
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        match self.list.insert_index(key, value) {
+            Some(index) => {
+                let (key, value) = self.list.tuple_at(index).unwrap();
+                self.index.insert(key, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the tuple, see `SortedList::remove`
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        match self.list.remove_index(key, value) {
+            Some((_, key, value)) => {
+                self.index.remove(&key, &value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns the first tuple, see `SortedList::pop_first`
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_first()?;
+        self.index.remove(&key, &value);
+        Some((key, value))
+    }
+
+    /// Removes and returns the last tuple, see `SortedList::pop_last`
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_last()?;
+        self.index.remove(&key, &value);
+        Some((key, value))
+    }
+
+    /// Removes all tuples
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.index.clear();
+    }
+
+    /// Returns the keys `value` is stored under, in order
+    pub fn keys_of(&self, value: &V) -> &[K] {
+        self.index.keys_of(value)
+    }
+
+    /// Returns `true` if `value` is stored under any key
+    pub fn contains_value(&self, value: &V) -> bool {
+        !self.keys_of(value).is_empty()
+    }
+
+    /// Returns the positions of all of the tuples with `value` in ascending order, as used by
+    /// `Cursor::index`
+    pub fn positions_of(&self, value: &V) -> Vec<usize> {
+        self.keys_of(value)
+            .iter()
+            .filter_map(|key| self.list.position_of(key, value))
+            .collect()
+    }
+
+    /// Returns the list, dropping the index
+    pub fn into_inner(self) -> SortedList<K, V> {
+        self.list
+    }
+}
+
+impl<K: Ord, V: PartialEq, I: ReverseIndex<K, V>> Default for IndexedSortedList<K, V, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V: PartialEq, I: ReverseIndex<K, V>> From<SortedList<K, V>>
+    for IndexedSortedList<K, V, I>
+{
+    fn from(list: SortedList<K, V>) -> Self {
+        let mut index = I::default();
+        for (key, value) in list.iter() {
+            index.insert(key, value);
+        }
+
+        IndexedSortedList { list, index }
+    }
+}
+
+impl<K: Ord, V: PartialEq, I> Deref for IndexedSortedList<K, V, I> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug, I> fmt::Debug for IndexedSortedList<K, V, I> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "IndexedSortedList {{ {:?} }}", self.list.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::{HashIndex, IndexedSortedList, OrdIndex, ReverseIndex};
+
+    fn brute_force_keys(list: &SortedList<u32, u32>, value: u32) -> Vec<u32> {
+        list.iter()
+            .filter(|&(_, v)| *v == value)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+
+    fn follows_changes<I: ReverseIndex<u32, u32>>() {
+        let mut list: IndexedSortedList<u32, u32, I> = IndexedSortedList::new();
+
+        for i in 0..200u32 {
+            list.insert(i % 17, i % 5);
+        }
+        for i in (0..200u32).step_by(3) {
+            list.remove(&(i % 17), &(i % 5));
+        }
+        list.pop_first();
+        list.pop_last();
+
+        for value in 0..6 {
+            assert_eq!(list.keys_of(&value), &brute_force_keys(&list, value)[..]);
+
+            let positions = list
+                .iter()
+                .enumerate()
+                .filter(|&(_, (_, v))| *v == value)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            assert_eq!(list.positions_of(&value), positions);
+        }
+
+        list.clear();
+        assert!(!list.contains_value(&0));
+    }
+
+    #[test]
+    fn hash_index_follows_changes() {
+        follows_changes::<HashIndex<u32, u32>>();
+    }
+
+    #[test]
+    fn ord_index_follows_changes() {
+        follows_changes::<OrdIndex<u32, u32>>();
+    }
+
+    #[test]
+    fn from_list() {
+        let list = vec![(2u32, 1u32), (1, 1), (1, 2), (3, 1)]
+            .into_iter()
+            .collect::<SortedList<_, _>>();
+        let list: IndexedSortedList<_, _> = IndexedSortedList::from(list);
+
+        assert_eq!(list.keys_of(&1), &[1, 2, 3]);
+        assert_eq!(list.positions_of(&1), vec![0, 2, 3]);
+        assert_eq!(list.keys_of(&3).len(), 0);
+    }
+}
@@ -0,0 +1,305 @@
+//! `SortedList` keyed by intervals, answering overlap and stabbing queries.
+
+use std::fmt;
+use std::ops::{Deref, Range};
+
+use super::SortedList;
+
+/// `SortedList` keyed by half-open `(start, end)` intervals, with any number of values per
+/// interval.
+///
+/// The intervals are ordered by their start like in a `SortedList`, and an implicit balanced tree
+/// over the positions records the interval with the largest end below every node. Queries skip
+/// every subtree which ends before the query range, so an `overlapping` query takes
+/// O(log n) time plus O(log n) per reported interval at most, and close to O(log n + k) when the
+/// intervals have similar lengths. Inserting and removing rebuild the index in O(n), which is the
+/// same as the cost of shifting the tuples.
+///
+/// All of the read-only methods of `SortedList` are available through `Deref`.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::SortedIntervalList;
+///
+/// let mut list = SortedIntervalList::new();
+/// list.insert((0u32, 10), "long");
+/// list.insert((2, 4), "short");
+/// list.insert((5, 7), "other");
+///
+/// assert_eq!(
+///     list.stabbing(3).map(|(_, v)| *v).collect::<Vec<_>>(),
+///     vec!["long", "short"]);
+/// assert_eq!(
+///     list.overlapping(4..6).map(|(_, v)| *v).collect::<Vec<_>>(),
+///     vec!["long", "other"]);
+/// ```
+pub struct SortedIntervalList<T: Ord, V: PartialEq> {
+    list: SortedList<(T, T), V>,
+    /// for the subtree rooted at every position, the position of the interval with the largest
+    /// end in it
+    max_end: Vec<usize>,
+}
+
+impl<T: Ord, V: PartialEq> SortedIntervalList<T, V> {
+    /// Creates a new empty list
+    pub fn new() -> Self {
+        SortedIntervalList {
+            list: SortedList::new(),
+            max_end: Vec::new(),
+        }
+    }
+
+    /// Inserts the value for the half-open interval `start..end`, returning `false` if the same
+    /// tuple already exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is greater than `end`.
+    pub fn insert(&mut self, interval: (T, T), value: V) -> bool {
+        assert!(
+            interval.0 <= interval.1,
+            "interval must not end before it starts"
+        );
+
+        let inserted = self.list.insert(interval, value);
+        if inserted {
+            self.rebuild();
+        }
+        inserted
+    }
+
+    /// Removes the tuple, returning `true` if it existed
+    pub fn remove(&mut self, interval: &(T, T), value: &V) -> bool {
+        let removed = self.list.remove(interval, value);
+        if removed {
+            self.rebuild();
+        }
+        removed
+    }
+
+    /// Returns the tuples whose interval overlaps the half-open `range`, in the order of the
+    /// list. Empty intervals never overlap anything, and neither does an empty `range`.
+    pub fn overlapping(&self, range: Range<T>) -> Overlaps<'_, T, V> {
+        let bound = if range.start < range.end {
+            self.list.partition_point(|k| k.0 < range.end)
+        } else {
+            0
+        };
+        Overlaps::new(self, bound, range.start)
+    }
+
+    /// Returns the tuples whose interval contains `point`, in the order of the list
+    pub fn stabbing(&self, point: T) -> Overlaps<'_, T, V> {
+        let bound = self.list.partition_point(|k| k.0 <= point);
+        Overlaps::new(self, bound, point)
+    }
+
+    /// Returns the list, dropping the index
+    pub fn into_inner(self) -> SortedList<(T, T), V> {
+        self.list
+    }
+
+    fn rebuild(&mut self) {
+        let len = self.list.len();
+        self.max_end.clear();
+        self.max_end.resize(len, 0);
+        self.build(0, len);
+    }
+
+    /// Fills the index for the subtree of `lo..hi`, returning the position of its largest end
+    fn build(&mut self, lo: usize, hi: usize) -> Option<usize> {
+        if lo >= hi {
+            return None;
+        }
+
+        let mid = lo + (hi - lo) / 2;
+        let mut max = mid;
+        let children = [self.build(lo, mid), self.build(mid + 1, hi)];
+        for &child in children.iter().flatten() {
+            if self.list.keys[child].1 > self.list.keys[max].1 {
+                max = child;
+            }
+        }
+
+        self.max_end[mid] = max;
+        Some(max)
+    }
+
+    fn end_below(&self, node: usize) -> &T {
+        &self.list.keys[self.max_end[node]].1
+    }
+}
+
+impl<T: Ord, V: PartialEq> Default for SortedIntervalList<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, V: PartialEq> Deref for SortedIntervalList<T, V> {
+    type Target = SortedList<(T, T), V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<T: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for SortedIntervalList<T, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "SortedIntervalList {{ {:?} }}", self.list.iter())
+    }
+}
+
+/// Iterator over the tuples of a `SortedIntervalList` matching a query, created by
+/// `overlapping` and `stabbing`
+pub struct Overlaps<'a, T: Ord + 'a, V: PartialEq + 'a> {
+    list: &'a SortedIntervalList<T, V>,
+    /// positions from `bound` onwards start too late
+    bound: usize,
+    /// intervals must end after this
+    after: T,
+    /// positions to report next, each with the end of the subtree on its right
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a, T: Ord, V: PartialEq> Overlaps<'a, T, V> {
+    fn new(list: &'a SortedIntervalList<T, V>, bound: usize, after: T) -> Self {
+        let mut overlaps = Overlaps {
+            list,
+            bound,
+            after,
+            stack: Vec::new(),
+        };
+        overlaps.descend(0, list.len());
+        overlaps
+    }
+
+    /// Pushes the leftmost path of the subtree of `lo..hi`, skipping everything which cannot
+    /// match
+    fn descend(&mut self, lo: usize, mut hi: usize) {
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if *self.list.end_below(mid) <= self.after {
+                return;
+            }
+
+            if mid < self.bound {
+                self.stack.push((mid, hi));
+            }
+            hi = mid;
+        }
+    }
+}
+
+impl<'a, T: Ord, V: PartialEq> Iterator for Overlaps<'a, T, V> {
+    type Item = (&'a (T, T), &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((mid, hi)) = self.stack.pop() {
+            self.descend(mid + 1, hi);
+
+            let (interval, value) = self.list.list.tuple_at(mid).unwrap();
+            if interval.1 > self.after && interval.0 < interval.1 {
+                return Some((interval, value));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: Ord, V: PartialEq> fmt::Debug for Overlaps<'a, T, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Overlaps {{ pending: {} }}", self.stack.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedIntervalList;
+
+    fn sample() -> SortedIntervalList<u32, usize> {
+        // deterministic pseudo random intervals of varying lengths
+        let mut list = SortedIntervalList::new();
+        let mut seed = 17u32;
+        for i in 0..500 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let start = (seed >> 8) % 1000;
+            let len = (seed >> 20) % if i % 10 == 0 { 300 } else { 20 };
+            list.insert((start, start + len), i);
+        }
+        list
+    }
+
+    #[test]
+    fn overlapping_matches_brute_force() {
+        let list = sample();
+
+        for &(start, end) in &[
+            (0, 1),
+            (100, 150),
+            (500, 501),
+            (990, 2000),
+            (3, 3),
+            (0, 2000),
+        ] {
+            let expected = list
+                .iter()
+                .filter(|&(k, _)| start < end && k.0 < k.1 && k.0 < end && k.1 > start)
+                .collect::<Vec<_>>();
+            assert_eq!(list.overlapping(start..end).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn stabbing_matches_brute_force() {
+        let list = sample();
+
+        for point in (0..1100).step_by(37) {
+            let expected = list
+                .iter()
+                .filter(|&(k, _)| k.0 <= point && k.1 > point)
+                .collect::<Vec<_>>();
+            assert_eq!(list.stabbing(point).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn multiple_values_and_removal() {
+        let mut list = SortedIntervalList::new();
+        assert!(list.insert((1u32, 5), 'a'));
+        assert!(list.insert((1, 5), 'b'));
+        assert!(!list.insert((1, 5), 'b'));
+        assert!(list.insert((3, 3), 'c'));
+
+        assert_eq!(
+            list.stabbing(3).collect::<Vec<_>>(),
+            vec![(&(1, 5), &'a'), (&(1, 5), &'b')]
+        );
+
+        assert!(list.remove(&(1, 5), &'a'));
+        assert_eq!(list.stabbing(4).collect::<Vec<_>>(), vec![(&(1, 5), &'b')]);
+        assert_eq!(list.overlapping(5..10).count(), 0);
+    }
+
+    #[test]
+    fn empty_intervals_and_ranges() {
+        let mut list = SortedIntervalList::new();
+        list.insert((0u32, 10), 'a');
+        list.insert((3, 3), 'b');
+        list.insert((4, 6), 'c');
+
+        assert_eq!(
+            list.overlapping(2..6).collect::<Vec<_>>(),
+            vec![(&(0, 10), &'a'), (&(4, 6), &'c')]
+        );
+        assert_eq!(
+            list.overlapping(3..4).collect::<Vec<_>>(),
+            vec![(&(0, 10), &'a')]
+        );
+        assert_eq!(list.stabbing(3).collect::<Vec<_>>(), vec![(&(0, 10), &'a')]);
+
+        assert_eq!(list.overlapping(5..5).count(), 0);
+        assert_eq!(list.overlapping(3..3).count(), 0);
+    }
+}
@@ -4,15 +4,78 @@
 
 //! Simple sorted list collection like the one found in the .NET collections library.
 
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::fmt;
 
 use std::ops::RangeBounds;
 
 use std::iter::FromIterator;
 
-/// `SortedList` stores multiple `(K, V)` tuples ordered by K, then in the order of insertion for `V`.
-/// Implmented using two `Vec` this should be fast for in-order inserts and quite bad in the
-/// worst-case of reverse insertion order.
+use std::marker::PhantomData;
+
+use std::mem;
+
+mod bi;
+mod bounded;
+mod cascade;
+mod composite;
+mod compressed;
+mod concurrent;
+mod cursor;
+mod diff;
+mod filter;
+mod frozen;
+mod indexed;
+mod interval;
+mod observe;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod persistent;
+mod prefix;
+mod search;
+mod sharded;
+mod transaction;
+mod ttl;
+mod window;
+
+pub use bi::BiSortedList;
+pub use bounded::{BoundedSortedList, EvictFn, EvictionPolicy, InsertOutcome};
+pub use cascade::CascadedSearch;
+pub use composite::TupleKey;
+pub use compressed::{ByteKey, CompressedSortedList, CompressedTuples};
+pub use concurrent::{ConcurrentSortedList, Snapshot};
+pub use cursor::{Cursor, CursorMut};
+pub use diff::{Change, Diff, PatchError};
+pub use filter::{FilteredSortedList, KeyFilter};
+pub use frozen::FrozenSortedList;
+pub use indexed::{HashIndex, IndexedSortedList, OrdIndex, ReverseIndex};
+pub use interval::{Overlaps, SortedIntervalList};
+pub use observe::{ChangeFeed, Event, ObservableSortedList, Observer};
+#[cfg(feature = "rayon")]
+pub use parallel::{IntoParTuples, ParGroups, ParTuples};
+pub use persistent::{PersistentSortedList, PersistentTuples};
+pub use prefix::StrKey;
+pub use search::{
+    BinarySearch, EytzingerSearch, InterpolationSearch, LearnedIndex, NumericKey, SearchStrategy,
+};
+pub use sharded::ShardedSortedList;
+pub use transaction::{Savepoint, Transaction};
+pub use ttl::{Clock, SystemClock, TtlSortedList, TtlTuples};
+pub use window::{WindowedSortedList, Windows};
+
+/// `SortedList` stores multiple `(K, V)` tuples ordered by K, then in the order of insertion for `V`
+/// unless another `ValueOrder` is chosen at construction.
+/// Implmented using two `VecDeque` this should be fast for in-order inserts and quite bad in the
+/// worst-case of reverse insertion order. Removing from either end is cheap, so the list can be
+/// used as a priority queue.
 ///
 /// # Example
 ///
@@ -29,27 +92,86 @@ use std::iter::FromIterator;
 ///     vec![(&0, &0), (&0, &2), (&1, &1)]);
 /// ```
 pub struct SortedList<K: Ord, V: PartialEq> {
-    keys: Vec<K>,
-    values: Vec<V>,
+    // both are always kept contiguous, so that they can be handed out as slices
+    keys: VecDeque<K>,
+    values: VecDeque<V>,
+    order: Order<V>,
+    // `None` searches with `slice::binary_search`
+    search: Option<Box<dyn SearchStrategy<K>>>,
 }
 
+/// The order in which the values of a single key are kept in `SortedList`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueOrder {
+    /// Values are kept in the order they were inserted in, which is the default
+    Insertion,
+    /// Values are kept sorted by `V: Ord`
+    Sorted,
+    /// Values are kept in reverse insertion order, most recently inserted first
+    MostRecentFirst,
+}
+
+/// `ValueOrder` with the comparison captured while `V: Ord` was known
+enum Order<V> {
+    Insertion,
+    Sorted(fn(&V, &V) -> Ordering),
+    MostRecentFirst,
+}
+
+impl<V> Order<V> {
+    fn value_order(&self) -> ValueOrder {
+        match *self {
+            Order::Insertion => ValueOrder::Insertion,
+            Order::Sorted(_) => ValueOrder::Sorted,
+            Order::MostRecentFirst => ValueOrder::MostRecentFirst,
+        }
+    }
+}
+
+impl<V> Clone for Order<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for Order<V> {}
+
 impl<K: Ord, V: PartialEq> SortedList<K, V> {
     /// Creates a new as small as possible `SortedList`
     pub fn new() -> Self {
-        SortedList {
-            keys: Vec::new(),
-            values: Vec::new(),
-        }
+        Self::with_order(Order::Insertion)
     }
 
     /// Creates `SortedList` with preallocated capacity of `len`
     pub fn with_capacity(len: usize) -> Self {
         SortedList {
-            keys: Vec::with_capacity(len),
-            values: Vec::with_capacity(len),
+            keys: VecDeque::with_capacity(len),
+            values: VecDeque::with_capacity(len),
+            order: Order::Insertion,
+            search: None,
         }
     }
 
+    /// Creates a new `SortedList` which keeps the values of each key in
+    /// `ValueOrder::MostRecentFirst` order.
+    pub fn most_recent_first() -> Self {
+        Self::with_order(Order::MostRecentFirst)
+    }
+
+    fn with_order(order: Order<V>) -> Self {
+        SortedList {
+            keys: VecDeque::new(),
+            values: VecDeque::new(),
+            order,
+            search: None,
+        }
+    }
+
+    /// Returns the order in which the values of each key are kept
+    pub fn value_order(&self) -> ValueOrder {
+        self.order.value_order()
+    }
+
     /// Returns the number of tuples
     pub fn len(&self) -> usize {
         self.keys.len()
@@ -61,40 +183,125 @@ impl<K: Ord, V: PartialEq> SortedList<K, V> {
     }
 
     /// Returns `true` if the `(key, value)` did not exist in the sorted list before and it exists now,
-    /// `false` otherwise.
+    /// `false` otherwise. The value is placed among the other values of `key` according to the
+    /// `ValueOrder` of the list.
     pub fn insert(&mut self, key: K, value: V) -> bool {
-        match self.keys.binary_search(&key) {
-            Ok(found_at) => {
-                let insertion_position = self.find_insertion_positition(found_at, &key, &value);
-
-                if let Some(insertion_position) = insertion_position {
-                    insertion_position.insert(key, value, &mut self.keys, &mut self.values);
-                    true
-                } else {
-                    false
-                }
+        self.insert_index(key, value).is_some()
+    }
+
+    /// Like `insert` but returns the index the tuple was inserted at.
+    fn insert_index(&mut self, key: K, value: V) -> Option<usize> {
+        match self.find_first_position(&key) {
+            Ok(first) => {
+                let insertion_position = match self.order {
+                    Order::Insertion => self.find_insertion_positition(first, &key, &value),
+                    Order::Sorted(cmp) => {
+                        let last = self.find_last_position(&key).unwrap();
+                        self.value_slice()[first..last]
+                            .binary_search_by(|other| cmp(other, &value))
+                            .err()
+                            .map(|at| InsertionPosition::Before(first + at + 1))
+                    }
+                    Order::MostRecentFirst => {
+                        if self.values_of(&key).contains(&value) {
+                            None
+                        } else {
+                            Some(InsertionPosition::Before(first + 1))
+                        }
+                    }
+                };
+
+                insertion_position.map(|insertion_position| {
+                    let index = insertion_position.index(self.len());
+                    self.insert_at(index, key, value);
+                    index
+                })
             }
             Err(insert_at) => {
-                self.keys.insert(insert_at, key);
-                self.values.insert(insert_at, value);
+                self.insert_at(insert_at, key, value);
 
-                true
+                Some(insert_at)
             }
         }
     }
 
-    /// Returns the values of a specific key as a slice
+    /// Returns `true` if the `(key, value)` existed in the sorted list and was removed, `false`
+    /// otherwise.
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        self.remove_index(key, value).is_some()
+    }
+
+    /// Like `remove` but returns the removed tuple and the index it was removed from.
+    fn remove_index(&mut self, key: &K, value: &V) -> Option<(usize, K, V)> {
+        let index = self.position_of(key, value)?;
+        let (key, value) = self.remove_at(index);
+        Some((index, key, value))
+    }
+
+    /// Returns the index of `(key, value)` if it exists.
+    fn position_of(&self, key: &K, value: &V) -> Option<usize> {
+        let first = self.find_first_position(key).ok()?;
+        self.values_of(key)
+            .iter()
+            .position(|other| other == value)
+            .map(|offset| first + offset)
+    }
+
+    /// Returns `true` if `value` can be placed right before the tuple at `index` without breaking
+    /// the order of keys, the `ValueOrder::Sorted` order of values or the order in which values
+    /// were inserted, which only allows placing the value last in its group, or first with
+    /// `ValueOrder::MostRecentFirst`.
+    fn fits_before(&self, index: usize, key: &K, value: &V) -> bool {
+        self.sorted_before(index, key, value)
+            && match self.order {
+                Order::Insertion => index == self.len() || self.keys[index] != *key,
+                Order::MostRecentFirst => index == 0 || self.keys[index - 1] != *key,
+                Order::Sorted(_) => true,
+            }
+    }
+
+    /// Like `fits_before` but places the value anywhere in its group unless the values are
+    /// `ValueOrder::Sorted`, as needed to recreate the exact order of another list.
+    fn sorted_before(&self, index: usize, key: &K, value: &V) -> bool {
+        let after_prev = index == 0 || self.keys[index - 1] <= *key;
+        let before_next = index == self.len() || *key <= self.keys[index];
+
+        if !after_prev || !before_next {
+            return false;
+        }
+
+        match self.order {
+            Order::Sorted(cmp) => {
+                let after_prev_value = index == 0
+                    || self.keys[index - 1] != *key
+                    || cmp(&self.values[index - 1], value) == Ordering::Less;
+                let before_next_value = index == self.len()
+                    || self.keys[index] != *key
+                    || cmp(value, &self.values[index]) == Ordering::Less;
+
+                after_prev_value && before_next_value
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the values of a specific key as a slice, in the `ValueOrder` of the list
     pub fn values_of(&self, key: &K) -> &[V] {
         let first = self.find_first_position(key).ok();
         match first {
             Some(first) => {
                 let last = self.find_last_position(key).unwrap();
-                &self.values[first..last]
+                &self.value_slice()[first..last]
             }
-            None => &self.values[0..0],
+            None => &self.value_slice()[0..0],
         }
     }
 
+    /// Returns `true` if there is at least one tuple with `key`
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find_first_position(key).is_ok()
+    }
+
     fn find_insertion_positition(
         &self,
         from: usize,
@@ -129,77 +336,371 @@ impl<K: Ord, V: PartialEq> SortedList<K, V> {
         }
     }
 
-    /// Iterate all stored tuples, keys in order, values in insertion order
-    pub fn iter(&self) -> Tuples<K, V> {
+    /// Iterate all stored tuples, keys in order, values in the `ValueOrder` of the list
+    pub fn iter(&self) -> Tuples<'_, K, V> {
         Tuples {
-            keys: &self.keys,
-            values: &self.values,
+            keys: self.key_slice(),
+            values: self.value_slice(),
             low: 0,
             high: self.len(),
         }
     }
 
     /// Iterate over all keys, can contain duplicates
-    pub fn keys(&self) -> ::std::slice::Iter<K> {
-        self.keys.iter()
+    pub fn keys(&self) -> ::std::slice::Iter<'_, K> {
+        self.key_slice().iter()
     }
 
     /// Iterate over all values
-    pub fn values(&self) -> ::std::slice::Iter<V> {
-        self.values.iter()
+    pub fn values(&self) -> ::std::slice::Iter<'_, V> {
+        self.value_slice().iter()
     }
 
-    /// Returns the first (in insertion order) value of `key`
+    /// Returns the first (in `ValueOrder`) value of `key`
     pub fn first_value_of(&self, key: &K) -> Option<&V> {
         self.find_first_position(key)
             .ok()
             .map(|idx| &self.values[idx])
     }
 
-    /// Returns the last (in insertion order) value of `key`
+    /// Returns the last (in `ValueOrder`) value of `key`
     pub fn last_value_of(&self, key: &K) -> Option<&V> {
         self.find_last_position(key)
             .ok()
             .map(|idx| &self.values[idx - 1])
     }
 
+    /// Returns the first tuple, which has the smallest key and the first value of it
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.tuple_at(0)
+    }
+
+    /// Returns the last tuple, which has the greatest key and the last value of it
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.len().checked_sub(1).and_then(|idx| self.tuple_at(idx))
+    }
+
+    /// Removes and returns the first tuple in amortized constant time
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value) = match (self.keys.pop_front(), self.values.pop_front()) {
+            (Some(k), Some(v)) => (k, v),
+            (None, None) => return None,
+            _ => unreachable!(),
+        };
+
+        self.keys_changed();
+        Some((key, value))
+    }
+
+    /// Removes and returns the last tuple in amortized constant time
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value) = match (self.keys.pop_back(), self.values.pop_back()) {
+            (Some(k), Some(v)) => (k, v),
+            (None, None) => return None,
+            _ => unreachable!(),
+        };
+
+        self.keys_changed();
+        Some((key, value))
+    }
+
+    /// Removes the smallest key and returns it with all of its values in the `ValueOrder` of the
+    /// list
+    pub fn pop_first_group(&mut self) -> Option<(K, Vec<V>)> {
+        let (key, value) = self.pop_first()?;
+        let mut values = vec![value];
+
+        while self.keys.front() == Some(&key) {
+            values.push(self.values.pop_front().unwrap());
+            self.keys.pop_front();
+            self.keys_changed();
+        }
+
+        Some((key, values))
+    }
+
+    /// Searches `key` with the `SearchStrategy` of the list, like `slice::binary_search`
+    fn search(&self, key: &K) -> Result<usize, usize> {
+        match self.search {
+            Some(ref search) => search.search(self.key_slice(), key),
+            None => self.key_slice().binary_search(key),
+        }
+    }
+
+    // galloping away from the found key bounds the search for the end of its group, so that large
+    // groups do not cost a second search over the whole list
     fn find_first_position(&self, key: &K) -> Result<usize, usize> {
-        match self.keys.binary_search(key) {
-            Ok(mut pos) => {
-                while pos > 0 && key == &self.keys[pos] {
-                    pos -= 1;
-                }
+        let keys = self.key_slice();
+        self.search(key).map(|pos| {
+            let mut step = 1;
+            while step <= pos && keys[pos - step] == *key {
+                step *= 2;
+            }
+            let low = pos.saturating_sub(step);
+            low + keys[low..pos].partition_point(|k| k < key)
+        })
+    }
 
-                if pos == 0 {
-                    if key == &self.keys[0] {
-                        Ok(0)
-                    } else {
-                        Ok(1)
-                    }
-                } else {
-                    Ok(pos + 1)
-                }
+    fn find_last_position(&self, key: &K) -> Result<usize, usize> {
+        let keys = self.key_slice();
+        self.search(key).map(|pos| {
+            let mut step = 1;
+            while pos + step < keys.len() && keys[pos + step] == *key {
+                step *= 2;
+            }
+            let high = keys.len().min(pos + step);
+            pos + 1 + keys[pos + 1..high].partition_point(|k| k <= key)
+        })
+    }
+
+    /// Returns the index of the first key for which `pred` returns `false`, assuming all keys
+    /// for which `pred` returns `true` come before the others, like with `slice::partition_point`.
+    pub fn partition_point<P>(&self, pred: P) -> usize
+    where
+        P: FnMut(&K) -> bool,
+    {
+        self.key_slice().partition_point(pred)
+    }
+
+    /// Moves every tuple with a key equal to or greater than `key` into a new `SortedList`.
+    /// Takes time proportional to the number of moved tuples.
+    pub fn split_off(&mut self, key: &K) -> SortedList<K, V> {
+        let at = self.find_first_position(key).either();
+        self.split_at_index(at)
+    }
+
+    /// Moves the tuples from `index` onwards into a new `SortedList`. If `index` is in the middle
+    /// of the values of a key, the values are divided between the lists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the list.
+    pub fn split_at_index(&mut self, index: usize) -> SortedList<K, V> {
+        assert!(index <= self.len(), "index out of bounds");
+
+        let mut tail = SortedList {
+            keys: self.keys.split_off(index),
+            values: self.values.split_off(index),
+            order: self.order,
+            search: self.search.as_ref().map(|search| search.box_clone()),
+        };
+
+        self.keys_changed();
+        tail.keys_changed();
+        tail
+    }
+
+    /// Starts recording changes to the list, which are undone unless the returned
+    /// `Transaction` is committed.
+    pub fn begin(&mut self) -> Transaction<'_, K, V> {
+        Transaction::new(self)
+    }
+
+    /// Runs `f` in a `Transaction` which is committed if `f` returns `Ok` and rolled back if it
+    /// returns `Err` or panics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let mut list = SortedList::new();
+    /// list.insert(1u32, 'a');
+    ///
+    /// let res = list.transaction(|tx| {
+    ///     tx.insert(2, 'b');
+    ///     if !tx.remove(&3, &'c') {
+    ///         return Err("missing 3");
+    ///     }
+    ///     Ok(())
+    /// });
+    ///
+    /// assert_eq!(res, Err("missing 3"));
+    /// assert_eq!(list.len(), 1);
+    /// ```
+    pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Transaction<'_, K, V>) -> Result<T, E>,
+    {
+        let mut tx = self.begin();
+        let ret = f(&mut tx);
+        if ret.is_ok() {
+            tx.commit();
+        }
+        ret
+    }
+
+    /// Returns a cursor pointing at the first tuple
+    pub fn cursor(&self) -> Cursor<'_, K, V> {
+        Cursor::new(self, 0)
+    }
+
+    /// Returns a mutable cursor pointing at the first tuple
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V> {
+        CursorMut::new(self, 0)
+    }
+
+    /// Returns the index of the first tuple with key equal to or greater than `key` and whether
+    /// `key` exists.
+    fn seek_position(&self, key: &K) -> (usize, bool) {
+        match self.find_first_position(key) {
+            Ok(pos) => (pos, true),
+            Err(pos) => (pos, false),
+        }
+    }
+
+    fn tuple_at(&self, index: usize) -> Option<(&K, &V)> {
+        match (self.keys.get(index), self.values.get(index)) {
+            (Some(k), Some(v)) => Some((k, v)),
+            (None, None) => None,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Inserts without checking the order, callers must make sure `index` is a valid position.
+    fn insert_at(&mut self, index: usize, key: K, value: V) {
+        self.keys.insert(index, key);
+        self.values.insert(index, value);
+
+        assert_eq!(self.keys.len(), self.values.len());
+
+        // inserting might have wrapped the ring buffers around
+        self.keys.make_contiguous();
+        self.values.make_contiguous();
+        self.keys_changed();
+    }
+
+    /// Appends without checking the order, callers must make sure the tuple belongs last.
+    fn push_unchecked(&mut self, key: K, value: V) {
+        let len = self.len();
+        self.insert_at(len, key, value);
+    }
+
+    fn remove_at(&mut self, index: usize) -> (K, V) {
+        let key = self.keys.remove(index).unwrap();
+        let value = self.values.remove(index).unwrap();
+
+        // removing from the middle might have wrapped the ring buffers around
+        self.keys.make_contiguous();
+        self.values.make_contiguous();
+        self.keys_changed();
+
+        (key, value)
+    }
+
+    /// Removes the tuples at `positions`, which must be in ascending order, moving the tuples
+    /// after the first of them only once.
+    fn remove_positions(&mut self, positions: &[usize]) {
+        let first = match positions.first() {
+            Some(&first) => first,
+            None => return,
+        };
+
+        let mut removed = positions.iter().peekable();
+        let mut kept = first;
+        for index in first..self.len() {
+            if removed.peek() == Some(&&index) {
+                removed.next();
+                continue;
             }
-            Err(pos) => Err(pos),
+
+            self.keys.swap(kept, index);
+            self.values.swap(kept, index);
+            kept += 1;
         }
+
+        self.keys.truncate(kept);
+        self.values.truncate(kept);
+        self.keys_changed();
     }
 
-    fn find_last_position(&self, key: &K) -> Result<usize, usize> {
-        match self.keys.binary_search(key) {
-            Ok(mut pos) => {
-                while pos < self.keys.len() && key == &self.keys[pos] {
-                    pos += 1;
-                }
+    /// Tells the `SearchStrategy` of the list that the keys changed
+    fn keys_changed(&mut self) {
+        if let Some(ref mut search) = self.search {
+            let (keys, rest) = self.keys.as_slices();
+            debug_assert!(rest.is_empty());
+            search.changed(keys);
+        }
+    }
 
-                if pos == self.keys.len() {
-                    // this is off by one ...
-                    Ok(pos)
-                } else {
-                    Ok(pos)
+    fn key_slice(&self) -> &[K] {
+        let (keys, rest) = self.keys.as_slices();
+        debug_assert!(rest.is_empty());
+        keys
+    }
+
+    fn value_slice(&self) -> &[V] {
+        let (values, rest) = self.values.as_slices();
+        debug_assert!(rest.is_empty());
+        values
+    }
+
+    /// Merges `incoming` in a single pass, placing the values of each key as if they were inserted
+    /// one by one. `incoming` must be sorted by key, keeping the order of the values of each key.
+    /// Returns the indices of the inserted tuples in the resulting list in ascending order.
+    fn merge_sorted(&mut self, incoming: Vec<(K, V)>) -> Vec<usize> {
+        let search = self.search.take();
+        let existing = mem::replace(
+            self,
+            SortedList {
+                keys: VecDeque::new(),
+                values: VecDeque::new(),
+                order: self.order,
+                search,
+            },
+        );
+
+        self.keys.reserve(existing.len() + incoming.len());
+        self.values.reserve(existing.len() + incoming.len());
+
+        let mut existing = existing.into_iter().peekable();
+        let mut incoming = incoming.into_iter().peekable();
+
+        let mut inserted = Vec::new();
+        // inserted indices of the last key, which can still move when more of its values arrive
+        let mut last_key_from = 0;
+
+        loop {
+            let take_existing = match (existing.peek(), incoming.peek()) {
+                (Some(old), Some(new)) => old.0 <= new.0,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            if take_existing {
+                // existing tuples are already unique and in order
+                let (k, v) = existing.next().unwrap();
+                self.push_unchecked(k, v);
+                continue;
+            }
+
+            let (k, v) = incoming.next().unwrap();
+
+            if inserted.last().is_some_and(|&at| self.keys[at] != k) {
+                last_key_from = inserted.len();
+            }
+
+            // the key is at the end, so this finds the place within the last key only
+            if let Some(at) = self.insert_index(k, v) {
+                for other in &mut inserted[last_key_from..] {
+                    if *other >= at {
+                        *other += 1;
+                    }
                 }
+                inserted.push(at);
             }
-            Err(pos) => Err(pos),
         }
+
+        inserted.sort_unstable();
+        inserted
+    }
+
+    /// Removes all tuples.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+        self.keys_changed();
     }
 
     /// Shrinks excess capacity from underlying vecs.
@@ -209,11 +710,41 @@ impl<K: Ord, V: PartialEq> SortedList<K, V> {
     }
 }
 
+impl<K: Ord, V: PartialEq> Default for SortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V: Ord> SortedList<K, V> {
+    /// Creates a new `SortedList` which keeps the values of each key in `ValueOrder::Sorted`
+    /// order. Looking up the insertion position within the values of a key is a binary search.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::{SortedList, ValueOrder};
+    ///
+    /// let mut list = SortedList::sorted_values();
+    /// list.insert(0u32, 'c');
+    /// list.insert(0, 'a');
+    /// list.insert(0, 'b');
+    ///
+    /// assert_eq!(list.value_order(), ValueOrder::Sorted);
+    /// assert_eq!(list.values_of(&0), &['a', 'b', 'c']);
+    /// ```
+    pub fn sorted_values() -> Self {
+        Self::with_order(Order::Sorted(Ord::cmp))
+    }
+}
+
 impl<K: Ord + Clone, V: PartialEq + Clone> Clone for SortedList<K, V> {
     fn clone(&self) -> Self {
         SortedList {
             keys: self.keys.clone(),
             values: self.values.clone(),
+            order: self.order,
+            search: self.search.as_ref().map(|search| search.box_clone()),
         }
     }
 }
@@ -245,7 +776,61 @@ impl<A> ResultExt<A> for Result<A, A> {
 
 impl<K: Ord + PartialEq, V: PartialEq> SortedList<K, V> {
     /// Returns an iterator over the specified range of tuples
-    pub fn range<R>(&self, range: R) -> Tuples<K, V>
+    pub fn range<R>(&self, range: R) -> Tuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let (low, high) = self.index_range(range);
+
+        Tuples {
+            keys: self.key_slice(),
+            values: self.value_slice(),
+            low,
+            high,
+        }
+    }
+
+    /// Removes the specified range of tuples, returning them as an iterator of owned tuples.
+    ///
+    /// The tuples are removed from the list already when `drain` is called, so the list stays
+    /// valid even if the iterator is dropped early or leaked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let mut list = vec![(1u32, 'a'), (2, 'b'), (2, 'c'), (3, 'd')]
+    ///     .into_iter()
+    ///     .collect::<SortedList<_, _>>();
+    ///
+    /// assert_eq!(list.drain(2..3).collect::<Vec<_>>(), vec![(2, 'b'), (2, 'c')]);
+    /// assert_eq!(list.len(), 2);
+    /// ```
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let (low, high) = self.index_range(range);
+
+        let keys = self.keys.drain(low..high).collect::<VecDeque<_>>();
+        let values = self.values.drain(low..high).collect::<VecDeque<_>>();
+
+        // draining from the middle might have wrapped the ring buffers around
+        self.keys.make_contiguous();
+        self.values.make_contiguous();
+        self.keys_changed();
+
+        Drain {
+            inner: IntoTuples {
+                keys: keys.into_iter(),
+                values: values.into_iter(),
+            },
+            _list: PhantomData,
+        }
+    }
+
+    fn index_range<R>(&self, range: R) -> (usize, usize)
     where
         R: RangeBounds<K>,
     {
@@ -263,14 +848,9 @@ impl<K: Ord + PartialEq, V: PartialEq> SortedList<K, V> {
         };
 
         let skip = start.unwrap_or(self.keys.len());
-        let take = if end <= skip { 0 } else { end };
+        let take = if end <= skip { skip } else { end };
 
-        Tuples {
-            keys: &self.keys,
-            values: &self.values,
-            low: skip,
-            high: take,
-        }
+        (skip, take)
     }
 }
 
@@ -288,8 +868,8 @@ impl<K: Ord, V: PartialEq> IntoIterator for SortedList<K, V> {
 
 /// IntoIterator version of `Tuples`
 pub struct IntoTuples<K, V> {
-    keys: ::std::vec::IntoIter<K>,
-    values: ::std::vec::IntoIter<V>,
+    keys: ::std::collections::vec_deque::IntoIter<K>,
+    values: ::std::collections::vec_deque::IntoIter<V>,
 }
 
 impl<K, V> fmt::Debug for IntoTuples<K, V> {
@@ -330,13 +910,45 @@ impl<K, V> DoubleEndedIterator for IntoTuples<K, V> {
 
 impl<K, V> ExactSizeIterator for IntoTuples<K, V> {}
 
+/// Iterator over tuples removed from a `SortedList` by `SortedList::drain`
+pub struct Drain<'a, K: 'a, V: 'a> {
+    inner: IntoTuples<K, V>,
+    _list: PhantomData<&'a mut (K, V)>,
+}
+
+impl<'a, K, V> fmt::Debug for Drain<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Drain {{ remaining: {} }}", self.inner.size_hint().0)
+    }
+}
+
+impl<'a, K, V> Iterator for Drain<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Drain<'a, K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Drain<'a, K, V> {}
+
 impl<K: Clone + Ord, V: PartialEq> Extend<(K, V)> for SortedList<K, V> {
     fn extend<T>(&mut self, iter: T)
     where
         T: IntoIterator<Item = (K, V)>,
     {
         let mut temp = iter.into_iter().collect::<Vec<_>>();
-        temp.sort_by_key(|&(ref k, _)| k.clone());
+        temp.sort_by_key(|(k, _)| k.clone());
 
         for (k, v) in temp {
             self.insert(k, v);
@@ -357,28 +969,18 @@ enum InsertionPosition {
 }
 
 impl InsertionPosition {
-    fn insert<K, V>(self, key: K, value: V, keys: &mut Vec<K>, values: &mut Vec<V>) {
+    fn index(self, len: usize) -> usize {
         match self {
-            InsertionPosition::Before(index) => {
-                keys.insert(index - 1, key);
-                values.insert(index - 1, value);
-
-                assert_eq!(keys.len(), values.len());
-            }
-            InsertionPosition::Last => {
-                keys.push(key);
-                values.push(value);
-
-                assert_eq!(keys.len(), values.len());
-            }
+            InsertionPosition::Before(index) => index - 1,
+            InsertionPosition::Last => len,
         }
     }
 }
 
 /// Iterator over tuples stored in `SortedList`
 pub struct Tuples<'a, K: 'a, V: 'a> {
-    keys: &'a Vec<K>,
-    values: &'a Vec<V>,
+    keys: &'a [K],
+    values: &'a [V],
     low: usize,
     high: usize,
 }
@@ -428,16 +1030,13 @@ impl<'a, K, V> Clone for Tuples<'a, K, V> {
 impl<'a, K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for Tuples<'a, K, V> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let remaining = self.size_hint().0;
-        let mut clone = self.clone();
-        let mut idx = 0;
         write!(fmt, "[")?;
-        while let Some(tuple) = clone.next() {
+        for (idx, tuple) in self.clone().enumerate() {
             if idx == remaining - 1 {
                 write!(fmt, "{:?}", tuple)?;
             } else {
                 write!(fmt, "{:?}, ", tuple)?;
             }
-            idx += 1;
         }
         write!(fmt, "]")
     }
@@ -651,7 +1250,6 @@ mod tests {
         it.map(|(a, b)| (*a, *b)).collect()
     }
 
-    #[cfg(feature = "nightly")]
     #[test]
     fn range() {
         use std::ops::Bound::*;
@@ -779,6 +1377,35 @@ mod tests {
         assert_eq!(to_vec(list.range(..)), to_vec(list.iter()));
     }
 
+    #[test]
+    fn empty_and_inverted_range_len() {
+        use std::ops::Bound::*;
+
+        let list = vec![(1u32, 'a'), (2, 'b'), (2, 'c'), (4, 'd')]
+            .into_iter()
+            .collect::<SortedList<_, _>>();
+
+        for &range in &[
+            (Included(2), Excluded(2)),
+            (Excluded(2), Included(2)),
+            (Included(3), Included(3)),
+            (Included(4), Excluded(1)),
+            (Excluded(2), Excluded(1)),
+            (Included(5), Unbounded),
+        ] {
+            let tuples = list.range(range);
+            assert_eq!(tuples.size_hint(), (0, Some(0)), "{:?}", range);
+            assert_eq!(tuples.len(), 0, "{:?}", range);
+            assert_eq!(tuples.count(), 0, "{:?}", range);
+        }
+
+        let empty = SortedList::<u32, u32>::new();
+        assert_eq!(
+            empty.range((Included(3), Excluded(1))).size_hint(),
+            (0, Some(0))
+        );
+    }
+
     #[test]
     fn first_value_of() {
         let mut list: SortedList<u32, u8> = SortedList::new();
@@ -942,12 +1569,278 @@ mod tests {
         );
     }
 
+    #[test]
+    fn first_and_last() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        assert_eq!(list.first(), None);
+        assert_eq!(list.last(), None);
+
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(2, 5);
+
+        assert_eq!(list.first(), Some((&0, &0)));
+        assert_eq!(list.last(), Some((&2, &5)));
+    }
+
+    #[test]
+    fn pop_first_and_last() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+
+        assert_eq!(list.pop_first(), Some((0, 0)));
+        assert_eq!(list.pop_last(), Some((2, 4)));
+
+        // inserting after popping from the front must keep the list in order
+        list.insert_only_new(5, 6);
+        list.insert_only_new(0, 2);
+        list.insert_only_new(3, 5);
+
+        assert_eq!(
+            to_vec(list.iter()),
+            vec![(0, 1), (0, 2), (1, 3), (3, 5), (5, 6)]
+        );
+        assert_eq!(list.values_of(&0), &[1, 2]);
+
+        assert_eq!(list.pop_first(), Some((0, 1)));
+        assert_eq!(list.pop_first(), Some((0, 2)));
+        assert_eq!(list.pop_first(), Some((1, 3)));
+        assert_eq!(list.pop_last(), Some((5, 6)));
+        assert_eq!(list.pop_last(), Some((3, 5)));
+        assert_eq!(list.pop_first(), None);
+        assert_eq!(list.pop_last(), None);
+    }
+
+    #[test]
+    fn pop_first_group() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(0, 2);
+
+        assert_eq!(list.pop_first_group(), Some((0, vec![0, 1, 2])));
+        assert_eq!(list.pop_first_group(), Some((1, vec![3])));
+        assert_eq!(list.pop_first_group(), Some((2, vec![4])));
+        assert_eq!(list.pop_first_group(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn scheduler_churn_keeps_order() {
+        // interleave pops from the front with inserts so the ring buffers wrap around
+        let mut list: SortedList<u32, u32> = SortedList::with_capacity(8);
+        let mut popped = Vec::new();
+
+        for round in 0..100u32 {
+            list.insert_only_new(round + 3, round);
+            list.insert_only_new(round + 1, round + 1000);
+            if round % 3 == 0 {
+                popped.push(list.pop_first().unwrap().0);
+            }
+
+            let keys = list.keys().cloned().collect::<Vec<_>>();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+        }
+
+        let mut sorted = popped.clone();
+        sorted.sort();
+        assert_eq!(popped, sorted);
+    }
+
+    #[test]
+    fn split_off() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(4, 6);
+        list.insert_only_new(2, 5);
+
+        let mut tail = list.split_off(&2);
+        assert_eq!(to_vec(list.iter()), vec![(0, 0), (0, 1), (1, 3)]);
+        assert_eq!(to_vec(tail.iter()), vec![(2, 4), (2, 5), (4, 6)]);
+
+        let rest = tail.split_off(&3);
+        assert_eq!(to_vec(tail.iter()), vec![(2, 4), (2, 5)]);
+        assert_eq!(to_vec(rest.iter()), vec![(4, 6)]);
+
+        assert!(tail.split_off(&5).is_empty());
+        assert_eq!(tail.split_off(&0).len(), 2);
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    fn split_at_index() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(1, 3);
+
+        let tail = list.split_at_index(1);
+        assert_eq!(to_vec(list.iter()), vec![(0, 0)]);
+        assert_eq!(to_vec(tail.iter()), vec![(0, 1), (1, 3)]);
+        assert_eq!(tail.values_of(&0), &[1]);
+    }
+
+    #[test]
+    fn partition_point() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+
+        assert_eq!(list.partition_point(|k| *k < 1), 2);
+        assert_eq!(list.partition_point(|k| *k < 10), 4);
+        assert_eq!(list.partition_point(|_| false), 0);
+    }
+
+    #[test]
+    fn drain_range() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(4, 6);
+        list.insert_only_new(2, 5);
+
+        {
+            let mut drain = list.drain(1..=2);
+            assert_eq!(drain.len(), 3);
+            assert_eq!(drain.next_back(), Some((2, 5)));
+            assert_eq!(drain.next(), Some((1, 3)));
+            assert_eq!(drain.len(), 1);
+        }
+
+        assert_eq!(to_vec(list.iter()), vec![(0, 0), (0, 1), (4, 6)]);
+
+        // inserting after draining from the middle must keep the list in order
+        list.insert_only_new(3, 7);
+        list.insert_only_new(0, 2);
+        assert_eq!(list.values_of(&0), &[0, 1, 2]);
+        assert_eq!(
+            to_vec(list.iter()),
+            vec![(0, 0), (0, 1), (0, 2), (3, 7), (4, 6)]
+        );
+
+        assert_eq!(list.drain(5..).count(), 0);
+        assert_eq!(list.drain(..).count(), 5);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn leaked_drain_leaves_list_valid() {
+        let mut list: SortedList<u32, String> = SortedList::new();
+        list.insert(0, "a".to_owned());
+        list.insert(1, "b".to_owned());
+        list.insert(2, "c".to_owned());
+
+        ::std::mem::forget(list.drain(1..2));
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.values_of(&1).len(), 0);
+        assert_eq!(list.last_value_of(&2).map(|s| s.as_str()), Some("c"));
+    }
+
+    #[test]
+    fn remove() {
+        let mut list: SortedList<u32, u8> = SortedList::new();
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(0, 2);
+
+        assert!(list.remove(&0, &1));
+        assert!(!list.remove(&0, &1));
+        assert!(!list.remove(&2, &1));
+        assert_eq!(to_vec(list.iter()), vec![(0, 0), (0, 2), (1, 3)]);
+
+        assert!(list.remove(&1, &3));
+        assert_eq!(list.values_of(&1).len(), 0);
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn sorted_values() {
+        use super::ValueOrder;
+
+        let mut list: SortedList<u32, u8> = SortedList::sorted_values();
+        assert_eq!(list.value_order(), ValueOrder::Sorted);
+
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 9);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(0, 5);
+        list.insert_only_new(0, 0);
+        assert!(!list.insert(0, 5));
+
+        assert_eq!(list.values_of(&0), &[0, 1, 5, 9]);
+        assert_eq!(list.first_value_of(&0), Some(&0));
+        assert_eq!(list.last_value_of(&0), Some(&9));
+        assert_eq!(
+            to_vec(list.iter()),
+            vec![(0, 0), (0, 1), (0, 5), (0, 9), (1, 3), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn most_recent_first() {
+        use super::ValueOrder;
+
+        let mut list: SortedList<u32, u8> = SortedList::most_recent_first();
+        assert_eq!(list.value_order(), ValueOrder::MostRecentFirst);
+
+        list.insert_only_new(1, 3);
+        list.insert_only_new(0, 1);
+        list.insert_only_new(0, 0);
+        list.insert_only_new(2, 4);
+        list.insert_only_new(0, 2);
+        assert!(!list.insert(0, 1));
+
+        assert_eq!(list.values_of(&0), &[2, 0, 1]);
+        assert_eq!(list.first_value_of(&0), Some(&2));
+        assert_eq!(list.last_value_of(&0), Some(&1));
+    }
+
+    #[test]
+    fn duplicate_detected_anywhere_in_group() {
+        let mut list: SortedList<u32, u32> = SortedList::new();
+        for value in 0..100 {
+            list.insert_only_new(0, value);
+        }
+
+        for value in 0..100 {
+            assert!(!list.insert(0, value));
+        }
+        assert_eq!(list.len(), 100);
+    }
+
+    #[test]
+    fn value_order_survives_split() {
+        let mut list: SortedList<u32, u8> = SortedList::sorted_values();
+        list.insert_only_new(0, 1);
+        list.insert_only_new(1, 1);
+
+        let mut tail = list.split_off(&1);
+        tail.insert_only_new(1, 0);
+        assert_eq!(tail.values_of(&1), &[0, 1]);
+    }
+
     #[test]
     fn from_iter() {
-        let coll = (0..20)
-            .into_iter()
-            .map(|x| (x, x + 5))
-            .collect::<SortedList<_, _>>();
+        let coll = (0..20).map(|x| (x, x + 5)).collect::<SortedList<_, _>>();
         assert_eq!(coll.len(), 20);
     }
 }
@@ -0,0 +1,394 @@
+//! Notifications of changes made to a `SortedList`.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::mpsc::{channel, Receiver, Sender, TryIter};
+
+use super::SortedList;
+
+/// A change made to an `ObservableSortedList`, with the position it was made at.
+///
+/// Observers receive the events with borrowed keys and values as `Event<&K, &V>` while a
+/// `ChangeFeed` receives owned clones as `Event<K, V>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<K, V> {
+    /// The tuple was inserted at `index`, moving the tuples from `index` onwards one forward
+    Inserted {
+        /// Key of the inserted tuple
+        key: K,
+        /// Value of the inserted tuple
+        value: V,
+        /// Index of the inserted tuple
+        index: usize,
+    },
+    /// The tuple was removed from `index`, moving the tuples after it one back
+    Removed {
+        /// Key of the removed tuple
+        key: K,
+        /// Value of the removed tuple
+        value: V,
+        /// Index the tuple was removed from
+        index: usize,
+    },
+    /// All tuples were removed
+    Cleared,
+    /// Many tuples were merged into the list at once
+    Merged {
+        /// The new tuples with their indices in the resulting list, in ascending order of index.
+        /// Applying them as insertions in this order reproduces the list.
+        tuples: Vec<(usize, K, V)>,
+    },
+}
+
+impl<'a, K: Clone, V: Clone> Event<&'a K, &'a V> {
+    /// Clones the key and value of the event
+    pub fn cloned(&self) -> Event<K, V> {
+        match *self {
+            Event::Inserted { key, value, index } => Event::Inserted {
+                key: key.clone(),
+                value: value.clone(),
+                index,
+            },
+            Event::Removed { key, value, index } => Event::Removed {
+                key: key.clone(),
+                value: value.clone(),
+                index,
+            },
+            Event::Cleared => Event::Cleared,
+            Event::Merged { ref tuples } => Event::Merged {
+                tuples: tuples
+                    .iter()
+                    .map(|&(index, key, value)| (index, key.clone(), value.clone()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Receives the events of an `ObservableSortedList` as they are applied.
+///
+/// Implemented for all `FnMut(&Event<&K, &V>)` closures.
+pub trait Observer<K, V> {
+    /// Called after the change described by `event` has been applied
+    fn notify(&mut self, event: &Event<&K, &V>);
+}
+
+impl<K, V, F> Observer<K, V> for F
+where
+    F: FnMut(&Event<&K, &V>),
+{
+    fn notify(&mut self, event: &Event<&K, &V>) {
+        self(event)
+    }
+}
+
+/// Wrapper around `SortedList` which notifies the subscribed observers of every change.
+///
+/// All of the read-only methods of `SortedList` are available through `Deref`.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::{Event, ObservableSortedList};
+///
+/// let mut list = ObservableSortedList::new();
+/// let feed = list.change_feed();
+///
+/// list.insert(2u32, 'a');
+/// list.insert(1, 'b');
+/// list.remove(&2, &'a');
+///
+/// assert_eq!(
+///     feed.try_iter().collect::<Vec<_>>(),
+///     vec![
+///         Event::Inserted { key: 2, value: 'a', index: 0 },
+///         Event::Inserted { key: 1, value: 'b', index: 0 },
+///         Event::Removed { key: 2, value: 'a', index: 1 },
+///     ]);
+/// ```
+pub struct ObservableSortedList<K: Ord, V: PartialEq> {
+    list: SortedList<K, V>,
+    observers: Vec<Box<dyn Observer<K, V> + Send>>,
+}
+
+impl<K: Ord, V: PartialEq> ObservableSortedList<K, V> {
+    /// Creates a new empty list without observers
+    pub fn new() -> Self {
+        Self::from(SortedList::new())
+    }
+
+    /// Adds an observer for all of the following changes
+    pub fn subscribe<O>(&mut self, observer: O)
+    where
+        O: Observer<K, V> + Send + 'static,
+    {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Inserts the tuple, see `SortedList::insert`
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        let index = match self.list.insert_index(key, value) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let (key, value) = self.list.tuple_at(index).unwrap();
+        notify(&mut self.observers, &Event::Inserted { key, value, index });
+        true
+    }
+
+    /// Removes the tuple, see `SortedList::remove`
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        match self.list.remove_index(key, value) {
+            Some((index, key, value)) => {
+                self.removed(index, &key, &value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns the first tuple, see `SortedList::pop_first`
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_first()?;
+        self.removed(0, &key, &value);
+        Some((key, value))
+    }
+
+    /// Removes and returns the last tuple, see `SortedList::pop_last`
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.list.pop_last()?;
+        let index = self.list.len();
+        self.removed(index, &key, &value);
+        Some((key, value))
+    }
+
+    /// Removes all tuples
+    pub fn clear(&mut self) {
+        self.list.clear();
+        notify(&mut self.observers, &Event::Cleared);
+    }
+
+    /// Inserts all of the tuples in a single merge, notifying the observers once with
+    /// `Event::Merged`
+    pub fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut incoming = iter.into_iter().collect::<Vec<_>>();
+        incoming.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let indices = self.list.merge_sorted(incoming);
+        if !indices.is_empty() {
+            let list = &self.list;
+            let tuples = indices
+                .into_iter()
+                .map(|index| {
+                    let (key, value) = list.tuple_at(index).unwrap();
+                    (index, key, value)
+                })
+                .collect();
+            notify(&mut self.observers, &Event::Merged { tuples });
+        }
+    }
+
+    /// Returns the list, dropping the observers
+    pub fn into_inner(self) -> SortedList<K, V> {
+        self.list
+    }
+
+    fn removed(&mut self, index: usize, key: &K, value: &V) {
+        notify(&mut self.observers, &Event::Removed { key, value, index });
+    }
+}
+
+impl<K: Ord + Clone + Send + 'static, V: PartialEq + Clone + Send + 'static>
+    ObservableSortedList<K, V>
+{
+    /// Returns a feed receiving clones of all of the following events. The feed can be moved to
+    /// another thread.
+    pub fn change_feed(&mut self) -> ChangeFeed<K, V> {
+        let (tx, rx) = channel();
+        self.subscribe(FeedSender(tx));
+        ChangeFeed(rx)
+    }
+}
+
+fn notify<K, V>(observers: &mut [Box<dyn Observer<K, V> + Send>], event: &Event<&K, &V>) {
+    for observer in observers {
+        observer.notify(event);
+    }
+}
+
+impl<K: Ord, V: PartialEq> Default for ObservableSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V: PartialEq> From<SortedList<K, V>> for ObservableSortedList<K, V> {
+    fn from(list: SortedList<K, V>) -> Self {
+        ObservableSortedList {
+            list,
+            observers: Vec::new(),
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> Deref for ObservableSortedList<K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for ObservableSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "ObservableSortedList {{ observers: {}, list: {:?} }}",
+            self.observers.len(),
+            self.list
+        )
+    }
+}
+
+struct FeedSender<K, V>(Sender<Event<K, V>>);
+
+impl<K: Clone, V: Clone> Observer<K, V> for FeedSender<K, V> {
+    fn notify(&mut self, event: &Event<&K, &V>) {
+        // the feed might have been dropped already, which is fine
+        let _ = self.0.send(event.cloned());
+    }
+}
+
+/// Channel receiving the events of an `ObservableSortedList`, created with
+/// `ObservableSortedList::change_feed`
+#[derive(Debug)]
+pub struct ChangeFeed<K, V>(Receiver<Event<K, V>>);
+
+impl<K, V> ChangeFeed<K, V> {
+    /// Returns the next event if there is one
+    pub fn try_recv(&self) -> Option<Event<K, V>> {
+        self.0.try_recv().ok()
+    }
+
+    /// Blocks until the next event, returning `None` once the list has been dropped
+    pub fn recv(&self) -> Option<Event<K, V>> {
+        self.0.recv().ok()
+    }
+
+    /// Iterates over the events received so far without blocking
+    pub fn try_iter(&self) -> TryIter<'_, Event<K, V>> {
+        self.0.try_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, ObservableSortedList};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Applies the events to a plain `Vec` like a view mirroring the list would
+    fn mirror(view: &mut Vec<(u32, u32)>, event: Event<u32, u32>) {
+        match event {
+            Event::Inserted { key, value, index } => view.insert(index, (key, value)),
+            Event::Removed { key, value, index } => assert_eq!(view.remove(index), (key, value)),
+            Event::Cleared => view.clear(),
+            Event::Merged { tuples } => {
+                for (index, key, value) in tuples {
+                    view.insert(index, (key, value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn view_follows_changes() {
+        let mut list = ObservableSortedList::from(
+            vec![(5u32, 5u32), (1, 1)]
+                .into_iter()
+                .collect::<super::SortedList<_, _>>(),
+        );
+        let mut view = list.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        let feed = list.change_feed();
+
+        list.insert(3, 3);
+        assert!(!list.insert(3, 3));
+        list.insert(0, 0);
+        list.remove(&5, &5);
+        list.pop_first();
+        list.pop_last();
+        list.extend(vec![(2, 2), (7, 7), (0, 1), (2, 0), (2, 2)]);
+        list.insert(9, 9);
+
+        // the view is rebuilt on another thread from the events alone
+        let view = thread::spawn(move || {
+            for event in feed.try_iter() {
+                mirror(&mut view, event);
+            }
+            (view, feed)
+        });
+        let (view, feed) = view.join().unwrap();
+
+        assert_eq!(view, list.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>());
+
+        list.clear();
+        assert_eq!(feed.try_recv(), Some(Event::Cleared));
+        assert_eq!(feed.try_recv(), None);
+    }
+
+    #[test]
+    fn merged_tuples_with_most_recent_first() {
+        let mut list = ObservableSortedList::from(super::SortedList::most_recent_first());
+        list.insert(1u32, 0u32);
+        list.insert(3, 0);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        {
+            let events = Arc::clone(&events);
+            list.subscribe(move |event: &Event<&u32, &u32>| {
+                events.lock().unwrap().push(event.cloned());
+            });
+        }
+
+        list.extend(vec![(1, 1), (1, 2), (2, 0), (4, 0)]);
+
+        assert_eq!(
+            list.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(1, 2), (1, 1), (1, 0), (2, 0), (3, 0), (4, 0)]
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![Event::Merged {
+                tuples: vec![(0, 1, 2), (1, 1, 1), (3, 2, 0), (5, 4, 0)]
+            }]
+        );
+    }
+
+    #[test]
+    fn feed_on_another_thread() {
+        let mut list = ObservableSortedList::new();
+        let feed = list.change_feed();
+
+        let consumer = thread::spawn(move || {
+            let mut seen = 0;
+            while let Some(event) = feed.recv() {
+                match event {
+                    Event::Inserted { .. } => seen += 1,
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            seen
+        });
+
+        for i in 0..100u32 {
+            list.insert(i % 10, i);
+        }
+        drop(list);
+
+        assert_eq!(consumer.join().unwrap(), 100);
+    }
+}
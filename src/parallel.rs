@@ -0,0 +1,254 @@
+//! Rayon parallel iterators over `SortedList`, enabled with the `rayon` feature.
+
+use std::ops::RangeBounds;
+
+use rayon::collections::vec_deque;
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{IntoParallelIterator, ParallelExtend, ParallelIterator, Zip};
+use rayon::prelude::*;
+use rayon::slice;
+
+use super::SortedList;
+
+/// Parallel iterator over borrowed tuples of a `SortedList`, which can split the values of a key
+/// between workers
+pub type ParTuples<'a, K, V> = Zip<slice::Iter<'a, K>, slice::Iter<'a, V>>;
+
+/// Parallel iterator over owned tuples of a `SortedList`
+pub type IntoParTuples<K, V> = Zip<vec_deque::IntoIter<K>, vec_deque::IntoIter<V>>;
+
+impl<'a, K: Ord + Sync, V: PartialEq + Sync> IntoParallelIterator for &'a SortedList<K, V> {
+    type Iter = ParTuples<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.key_slice()
+            .par_iter()
+            .zip(self.value_slice().par_iter())
+    }
+}
+
+impl<K: Ord + Send, V: PartialEq + Send> IntoParallelIterator for SortedList<K, V> {
+    type Iter = IntoParTuples<K, V>;
+    type Item = (K, V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.keys.into_par_iter().zip(self.values.into_par_iter())
+    }
+}
+
+impl<K: Ord + Sync, V: PartialEq + Sync> SortedList<K, V> {
+    /// Returns a parallel iterator over the specified range of tuples, see `range`
+    pub fn par_range<R>(&self, range: R) -> ParTuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let (low, high) = self.index_range(range);
+        self.key_slice()[low..high]
+            .par_iter()
+            .zip(self.value_slice()[low..high].par_iter())
+    }
+
+    /// Returns a parallel iterator over every key and all of its values. The work is only split
+    /// between the keys, so the values of a key are never divided between workers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate rayon;
+    /// # extern crate sorted_list;
+    /// use rayon::prelude::*;
+    /// use sorted_list::SortedList;
+    ///
+    /// # fn main() {
+    /// let list = (0..1000u32).map(|x| (x % 10, x)).collect::<SortedList<_, _>>();
+    ///
+    /// let sums = list
+    ///     .par_groups()
+    ///     .map(|(key, values)| (*key, values.iter().sum::<u32>()))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// assert_eq!(sums.len(), 10);
+    /// assert_eq!(sums[0], (0, 49500));
+    /// # }
+    /// ```
+    pub fn par_groups(&self) -> ParGroups<'_, K, V> {
+        ParGroups {
+            keys: self.key_slice(),
+            values: self.value_slice(),
+        }
+    }
+}
+
+/// Parallel iterator over the keys of a `SortedList` with all of their values, created by
+/// `SortedList::par_groups`
+#[derive(Debug)]
+pub struct ParGroups<'a, K: 'a, V: 'a> {
+    keys: &'a [K],
+    values: &'a [V],
+}
+
+impl<'a, K: Ord + Sync, V: Sync> ParallelIterator for ParGroups<'a, K, V> {
+    type Item = (&'a K, &'a [V]);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync> UnindexedProducer for ParGroups<'a, K, V> {
+    type Item = (&'a K, &'a [V]);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let len = self.keys.len();
+        if len < 2 {
+            return (self, None);
+        }
+
+        // move the midpoint to the start of its key, or to the end of it if the key spans the
+        // whole first half
+        let middle = &self.keys[len / 2];
+        let mut at = self.keys.partition_point(|k| k < middle);
+        if at == 0 {
+            at = self.keys.partition_point(|k| k <= middle);
+        }
+
+        if at == len {
+            return (self, None);
+        }
+
+        let (left_keys, right_keys) = self.keys.split_at(at);
+        let (left_values, right_values) = self.values.split_at(at);
+
+        (
+            ParGroups {
+                keys: left_keys,
+                values: left_values,
+            },
+            Some(ParGroups {
+                keys: right_keys,
+                values: right_values,
+            }),
+        )
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        let mut low = 0;
+        while low < self.keys.len() && !folder.full() {
+            let key = &self.keys[low];
+            let high = low + self.keys[low..].partition_point(|k| k == key);
+            folder = folder.consume((key, &self.values[low..high]));
+            low = high;
+        }
+        folder
+    }
+}
+
+impl<K: Ord + Send, V: PartialEq + Send> ParallelExtend<(K, V)> for SortedList<K, V> {
+    /// Sorts the new tuples in parallel and then merges them with the existing ones in a single
+    /// pass. The new values of each key are placed as if inserted one by one in iteration order.
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        let mut incoming = par_iter.into_par_iter().collect::<Vec<_>>();
+        // stable, so that the values of each key stay in iteration order
+        incoming.par_sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.merge_sorted(incoming);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use rayon::prelude::*;
+
+    fn sample() -> SortedList<u32, u32> {
+        (0..10_000u32).map(|x| (x % 97, x)).collect()
+    }
+
+    #[test]
+    fn par_iter_matches_iter() {
+        let list = sample();
+
+        let par = (&list)
+            .into_par_iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>();
+        let seq = list.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        assert_eq!(par, seq);
+
+        let owned = list.clone().into_par_iter().collect::<Vec<_>>();
+        assert_eq!(owned, seq);
+    }
+
+    #[test]
+    fn par_range_matches_range() {
+        let list = sample();
+
+        let par = list
+            .par_range(10..=20)
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>();
+        let seq = list
+            .range(10..=20)
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<_>>();
+        assert_eq!(par, seq);
+    }
+
+    #[test]
+    fn par_groups_never_divide_a_key() {
+        let list = sample();
+
+        let groups = list
+            .par_groups()
+            .map(|(k, values)| (*k, values.len()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(groups.len(), 97);
+        for (k, len) in groups {
+            assert_eq!(len, list.values_of(&k).len());
+        }
+
+        let single = (0..100u32).map(|x| (1u32, x)).collect::<SortedList<_, _>>();
+        assert_eq!(single.par_groups().count(), 1);
+
+        let empty: SortedList<u32, u32> = SortedList::new();
+        assert_eq!(empty.par_groups().count(), 0);
+    }
+
+    #[test]
+    fn par_extend_matches_extend() {
+        let mut par = (0..100u32)
+            .map(|x| (x % 7, x))
+            .collect::<SortedList<_, _>>();
+        let mut seq = par.clone();
+
+        let input = (50..500u32).map(|x| (x % 11, x)).collect::<Vec<_>>();
+        par.par_extend(input.clone());
+        seq.extend(input);
+
+        assert_eq!(
+            par.iter().collect::<Vec<_>>(),
+            seq.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn par_extend_respects_value_order() {
+        let mut list: SortedList<u32, u32> = SortedList::most_recent_first();
+        list.insert(1, 0);
+        list.par_extend(vec![(1, 1), (0, 5), (1, 2), (1, 0)]);
+
+        assert_eq!(list.values_of(&1), &[2, 1, 0]);
+        assert_eq!(list.values_of(&0), &[5]);
+    }
+}
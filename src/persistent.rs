@@ -0,0 +1,839 @@
+//! Immutable `SortedList` sharing structure between versions.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use super::{Order, ValueOrder};
+
+/// Maximum number of tuples in a leaf or children in a branch before it is split
+const MAX_LEN: usize = 32;
+
+/// Immutable version of `SortedList` where `insert` and `remove` return a new version and leave
+/// the old one as it was.
+///
+/// Every tuple is stored on its own in a B+ tree of reference counted nodes, so a new version
+/// copies only the `O(log n)` nodes on the path to the changed tuple and shares everything else
+/// with the old version, however many values the key has. Cloning a version is a reference count
+/// increment. Values of a key are kept in the `ValueOrder` chosen at construction, like with
+/// `SortedList`.
+///
+/// As with `SortedList`, checking that an inserted value is new reads the other values of its key
+/// unless the values are kept in `ValueOrder::Sorted` order. The values of a key are not next to
+/// each other in memory, so `values_of` returns an iterator instead of a slice.
+///
+/// Removing tuples never merges nodes, it only drops the ones which became empty.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::PersistentSortedList;
+///
+/// let empty = PersistentSortedList::new();
+/// let first = empty.insert(1u32, 'a');
+/// let second = first.insert(0, 'b').insert(1, 'c');
+/// let third = second.remove(&1, &'a');
+///
+/// assert!(empty.is_empty());
+/// assert_eq!(first.iter().collect::<Vec<_>>(), vec![(&1, &'a')]);
+/// assert_eq!(second.values_of(&1).collect::<Vec<_>>(), vec![&'a', &'c']);
+/// assert_eq!(third.iter().collect::<Vec<_>>(), vec![(&0, &'b'), (&1, &'c')]);
+/// ```
+pub struct PersistentSortedList<K, V> {
+    root: Option<Arc<Node<K, V>>>,
+    len: usize,
+    order: Order<V>,
+}
+
+/// A tuple, shared by the leaves of every version which has it
+type Tuple<K, V> = Arc<(K, V)>;
+
+enum Node<K, V> {
+    Leaf(Vec<Tuple<K, V>>),
+    Branch(Vec<Child<K, V>>),
+}
+
+/// A child of a branch with the number of tuples under it and the first one of them, so that
+/// positions and searches can be resolved without visiting the child
+struct Child<K, V> {
+    node: Arc<Node<K, V>>,
+    len: usize,
+    first: Tuple<K, V>,
+}
+
+enum Inserted<K, V> {
+    Replaced(Node<K, V>),
+    Split(Node<K, V>, Node<K, V>),
+}
+
+enum Removed<K, V> {
+    Replaced(Node<K, V>),
+    Emptied,
+}
+
+impl<K, V> Node<K, V> {
+    /// Returns the number of tuples or children directly in this node
+    fn width(&self) -> usize {
+        match *self {
+            Node::Leaf(ref tuples) => tuples.len(),
+            Node::Branch(ref children) => children.len(),
+        }
+    }
+
+    /// Returns the number of tuples under this node
+    fn len(&self) -> usize {
+        match *self {
+            Node::Leaf(ref tuples) => tuples.len(),
+            Node::Branch(ref children) => children.iter().map(|c| c.len).sum(),
+        }
+    }
+
+    /// Returns the first tuple under this node, which are never empty
+    fn first(&self) -> &Tuple<K, V> {
+        match *self {
+            Node::Leaf(ref tuples) => &tuples[0],
+            Node::Branch(ref children) => &children[0].first,
+        }
+    }
+
+    fn into_child(self) -> Child<K, V> {
+        Child {
+            len: self.len(),
+            first: Arc::clone(self.first()),
+            node: Arc::new(self),
+        }
+    }
+
+    fn get(&self, index: usize) -> &Tuple<K, V> {
+        match *self {
+            Node::Leaf(ref tuples) => &tuples[index],
+            Node::Branch(ref children) => {
+                let (at, offset) = locate(children, index);
+                children[at].node.get(offset)
+            }
+        }
+    }
+
+    /// Returns the position of the first tuple under this node for which `pred` returns `false`
+    fn partition_point<P>(&self, pred: &mut P) -> usize
+    where
+        P: FnMut(&K, &V) -> bool,
+    {
+        match *self {
+            Node::Leaf(ref tuples) => tuples.partition_point(|t| pred(&t.0, &t.1)),
+            Node::Branch(ref children) => {
+                let at = children.partition_point(|c| pred(&c.first.0, &c.first.1));
+                if at == 0 {
+                    return 0;
+                }
+
+                let before = children[..at - 1].iter().map(|c| c.len).sum::<usize>();
+                before + children[at - 1].node.partition_point(pred)
+            }
+        }
+    }
+
+    fn insert(&self, index: usize, tuple: Tuple<K, V>) -> Inserted<K, V> {
+        match *self {
+            Node::Leaf(ref tuples) => {
+                let mut tuples = tuples.clone();
+                tuples.insert(index, tuple);
+                split_if_full(tuples, Node::Leaf)
+            }
+            Node::Branch(ref children) => {
+                let (at, offset) = locate(children, index);
+                let mut children = children.clone();
+
+                match children[at].node.insert(offset, tuple) {
+                    Inserted::Replaced(child) => children[at] = child.into_child(),
+                    Inserted::Split(left, right) => {
+                        children[at] = left.into_child();
+                        children.insert(at + 1, right.into_child());
+                    }
+                }
+
+                split_if_full(children, Node::Branch)
+            }
+        }
+    }
+
+    fn remove(&self, index: usize) -> Removed<K, V> {
+        match *self {
+            Node::Leaf(ref tuples) => {
+                if tuples.len() == 1 {
+                    return Removed::Emptied;
+                }
+
+                let mut tuples = tuples.clone();
+                tuples.remove(index);
+                Removed::Replaced(Node::Leaf(tuples))
+            }
+            Node::Branch(ref children) => {
+                let (at, offset) = locate(children, index);
+                let mut children = children.clone();
+
+                match children[at].node.remove(offset) {
+                    Removed::Replaced(child) => children[at] = child.into_child(),
+                    Removed::Emptied => {
+                        children.remove(at);
+                    }
+                }
+
+                if children.is_empty() {
+                    Removed::Emptied
+                } else {
+                    Removed::Replaced(Node::Branch(children))
+                }
+            }
+        }
+    }
+}
+
+/// Returns the child holding the tuple at `index` and the position of the tuple in it. One past
+/// the last tuple belongs to the last child.
+fn locate<K, V>(children: &[Child<K, V>], mut index: usize) -> (usize, usize) {
+    for (at, child) in children.iter().enumerate() {
+        if index < child.len {
+            return (at, index);
+        }
+        index -= child.len;
+    }
+
+    let last = children.len() - 1;
+    (last, index + children[last].len)
+}
+
+fn split_if_full<T, K, V>(mut items: Vec<T>, node: fn(Vec<T>) -> Node<K, V>) -> Inserted<K, V> {
+    if items.len() > MAX_LEN {
+        let right = items.split_off(items.len() / 2);
+        Inserted::Split(node(items), node(right))
+    } else {
+        Inserted::Replaced(node(items))
+    }
+}
+
+impl<K, V> PersistentSortedList<K, V> {
+    /// Creates a new empty `PersistentSortedList`
+    pub fn new() -> Self {
+        Self::with_order(Order::Insertion)
+    }
+
+    /// Creates a new empty `PersistentSortedList` which keeps the values of each key in
+    /// `ValueOrder::MostRecentFirst` order.
+    pub fn most_recent_first() -> Self {
+        Self::with_order(Order::MostRecentFirst)
+    }
+
+    fn with_order(order: Order<V>) -> Self {
+        PersistentSortedList {
+            root: None,
+            len: 0,
+            order,
+        }
+    }
+
+    /// Returns the order in which the values of each key are kept
+    pub fn value_order(&self) -> ValueOrder {
+        self.order.value_order()
+    }
+
+    /// Returns the number of tuples
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the collection is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the first tuple, which has the smallest key and the first value of it
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.tuple_at(0)
+    }
+
+    /// Returns the last tuple, which has the greatest key and the last value of it
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.len
+            .checked_sub(1)
+            .and_then(|index| self.tuple_at(index))
+    }
+
+    /// Iterate all stored tuples, keys in order, values in the `ValueOrder` of the list
+    pub fn iter(&self) -> PersistentTuples<'_, K, V> {
+        self.tuples(0, self.len)
+    }
+
+    /// Iterate over all keys, can contain duplicates
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Iterate over all values
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, v)| v)
+    }
+
+    fn tuple_at(&self, index: usize) -> Option<(&K, &V)> {
+        match self.root {
+            Some(ref root) if index < self.len => {
+                let tuple = root.get(index);
+                Some((&tuple.0, &tuple.1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the tuples at positions `low..high`
+    fn tuples(&self, low: usize, high: usize) -> PersistentTuples<'_, K, V> {
+        match self.root {
+            Some(ref root) if low < high => PersistentTuples {
+                front: path(root, low),
+                back: path(root, high - 1),
+                remaining: high - low,
+            },
+            _ => PersistentTuples {
+                front: Vec::new(),
+                back: Vec::new(),
+                remaining: 0,
+            },
+        }
+    }
+
+    fn position<P>(&self, mut pred: P) -> usize
+    where
+        P: FnMut(&K, &V) -> bool,
+    {
+        self.root
+            .as_ref()
+            .map_or(0, |root| root.partition_point(&mut pred))
+    }
+
+    /// Returns a new version with `tuple` inserted at `index`
+    fn insert_at(&self, index: usize, tuple: Tuple<K, V>) -> Self {
+        let root = match self.root {
+            Some(ref root) => match root.insert(index, tuple) {
+                Inserted::Replaced(root) => root,
+                Inserted::Split(left, right) => {
+                    Node::Branch(vec![left.into_child(), right.into_child()])
+                }
+            },
+            None => Node::Leaf(vec![tuple]),
+        };
+
+        PersistentSortedList {
+            root: Some(Arc::new(root)),
+            len: self.len + 1,
+            order: self.order,
+        }
+    }
+
+    /// Returns a new version with the tuple at `index` removed
+    fn remove_at(&self, index: usize) -> Self {
+        let mut root = match self.root.as_ref().map(|root| root.remove(index)) {
+            Some(Removed::Replaced(root)) => Arc::new(root),
+            _ => return Self::with_order(self.order),
+        };
+
+        // collapse branches left with a single child
+        loop {
+            let only_child = match *root {
+                Node::Branch(ref children) if children.len() == 1 => Arc::clone(&children[0].node),
+                _ => break,
+            };
+            root = only_child;
+        }
+
+        PersistentSortedList {
+            root: Some(root),
+            len: self.len - 1,
+            order: self.order,
+        }
+    }
+}
+
+impl<K, V: Ord> PersistentSortedList<K, V> {
+    /// Creates a new empty `PersistentSortedList` which keeps the values of each key in
+    /// `ValueOrder::Sorted` order. Looking up a value of a key is then a search as well.
+    pub fn sorted_values() -> Self {
+        Self::with_order(Order::Sorted(Ord::cmp))
+    }
+}
+
+impl<K: Ord, V> PersistentSortedList<K, V> {
+    /// Returns the values of a specific key, in the `ValueOrder` of the list
+    pub fn values_of(&self, key: &K) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        let low = self.position(|k, _| k < key);
+        let high = self.position(|k, _| k <= key);
+        self.tuples(low, high).map(|(_, v)| v)
+    }
+
+    /// Returns `true` if there is at least one tuple with `key`
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.first_value_of(key).is_some()
+    }
+
+    /// Returns the first (in `ValueOrder`) value of `key`
+    pub fn first_value_of(&self, key: &K) -> Option<&V> {
+        match self.tuple_at(self.position(|k, _| k < key)) {
+            Some((k, v)) if k == key => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the last (in `ValueOrder`) value of `key`
+    pub fn last_value_of(&self, key: &K) -> Option<&V> {
+        let high = self.position(|k, _| k <= key);
+        match high.checked_sub(1).and_then(|index| self.tuple_at(index)) {
+            Some((k, v)) if k == key => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the first key for which `pred` returns `false`, see
+    /// `SortedList::partition_point`
+    pub fn partition_point<P>(&self, mut pred: P) -> usize
+    where
+        P: FnMut(&K) -> bool,
+    {
+        self.position(|k, _| pred(k))
+    }
+
+    /// Returns an iterator over the specified range of tuples
+    pub fn range<R>(&self, range: R) -> PersistentTuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        let low = match range.start_bound() {
+            Bound::Included(key) => self.position(|k, _| k < key),
+            Bound::Excluded(key) => self.position(|k, _| k <= key),
+            Bound::Unbounded => 0,
+        };
+
+        let high = match range.end_bound() {
+            Bound::Included(key) => self.position(|k, _| k <= key),
+            Bound::Excluded(key) => self.position(|k, _| k < key),
+            Bound::Unbounded => self.len,
+        };
+
+        self.tuples(low, high)
+    }
+}
+
+impl<K: Ord, V: PartialEq> PersistentSortedList<K, V> {
+    /// Returns a new version with `(key, value)` inserted, or a clone of this version if it
+    /// already existed. The value is placed among the other values of `key` according to the
+    /// `ValueOrder` of the list.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let index = match self.order {
+            Order::Sorted(cmp) => {
+                let at = self
+                    .position(|k, v| k < &key || (k == &key && cmp(v, &value) == Ordering::Less));
+                match self.tuple_at(at) {
+                    Some((k, v)) if *k == key && cmp(v, &value) == Ordering::Equal => {
+                        return self.clone()
+                    }
+                    _ => at,
+                }
+            }
+            Order::Insertion | Order::MostRecentFirst => {
+                if self.values_of(&key).any(|v| *v == value) {
+                    return self.clone();
+                }
+
+                match self.order {
+                    Order::MostRecentFirst => self.position(|k, _| k < &key),
+                    _ => self.position(|k, _| k <= &key),
+                }
+            }
+        };
+
+        self.insert_at(index, Arc::new((key, value)))
+    }
+
+    /// Returns a new version with `(key, value)` removed, or a clone of this version if it did not
+    /// exist.
+    pub fn remove(&self, key: &K, value: &V) -> Self {
+        match self.position_of(key, value) {
+            Some(index) => self.remove_at(index),
+            None => self.clone(),
+        }
+    }
+
+    /// Returns `true` if the `(key, value)` exists in this version
+    pub fn contains(&self, key: &K, value: &V) -> bool {
+        self.position_of(key, value).is_some()
+    }
+
+    /// Returns the index of `(key, value)` if it exists
+    fn position_of(&self, key: &K, value: &V) -> Option<usize> {
+        if let Order::Sorted(cmp) = self.order {
+            let at = self.position(|k, v| k < key || (k == key && cmp(v, value) == Ordering::Less));
+            return match self.tuple_at(at) {
+                Some((k, v)) if k == key && v == value => Some(at),
+                _ => None,
+            };
+        }
+
+        let low = self.position(|k, _| k < key);
+        let high = self.position(|k, _| k <= key);
+        self.tuples(low, high)
+            .position(|(_, v)| v == value)
+            .map(|offset| low + offset)
+    }
+}
+
+/// Returns the path from `root` to the tuple at `index`, with the position taken in every node
+fn path<K, V>(root: &Node<K, V>, mut index: usize) -> Vec<(&Node<K, V>, usize)> {
+    let mut path = Vec::new();
+    let mut node = root;
+
+    loop {
+        match *node {
+            Node::Leaf(_) => {
+                path.push((node, index));
+                return path;
+            }
+            Node::Branch(ref children) => {
+                let (at, offset) = locate(children, index);
+                path.push((node, at));
+                node = &children[at].node;
+                index = offset;
+            }
+        }
+    }
+}
+
+/// Moves the path to the next tuple, or to the previous one if `forward` is `false`
+fn step<K, V>(path: &mut Vec<(&Node<K, V>, usize)>, forward: bool) {
+    // climb up until a node has a next child or tuple in the direction
+    while let Some((node, at)) = path.pop() {
+        let (mut node, mut at) = match (forward, at) {
+            (true, at) if at + 1 < node.width() => (node, at + 1),
+            (false, at) if at > 0 => (node, at - 1),
+            _ => continue,
+        };
+
+        // and down again to the first or last tuple of the subtree
+        loop {
+            path.push((node, at));
+            match *node {
+                Node::Branch(ref children) => {
+                    node = &children[at].node;
+                    at = if forward { 0 } else { node.width() - 1 };
+                }
+                Node::Leaf(_) => return,
+            }
+        }
+    }
+}
+
+fn current<'a, K, V>(path: &[(&'a Node<K, V>, usize)]) -> (&'a K, &'a V) {
+    let (node, at) = *path
+        .last()
+        .expect("paths are not empty while tuples remain");
+    match *node {
+        Node::Leaf(ref tuples) => (&tuples[at].0, &tuples[at].1),
+        Node::Branch(_) => unreachable!("paths end at a leaf"),
+    }
+}
+
+impl<K, V> Clone for Child<K, V> {
+    fn clone(&self) -> Self {
+        Child {
+            node: Arc::clone(&self.node),
+            len: self.len,
+            first: Arc::clone(&self.first),
+        }
+    }
+}
+
+impl<K, V> Clone for PersistentSortedList<K, V> {
+    fn clone(&self) -> Self {
+        PersistentSortedList {
+            root: self.root.clone(),
+            len: self.len,
+            order: self.order,
+        }
+    }
+}
+
+impl<K, V> Default for PersistentSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V: PartialEq> FromIterator<(K, V)> for PersistentSortedList<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |list, (k, v)| list.insert(k, v))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for PersistentSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "PersistentSortedList ")?;
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over tuples stored in `PersistentSortedList`
+pub struct PersistentTuples<'a, K: 'a, V: 'a> {
+    /// Nodes on the path to the next tuple from the front, with the position taken in each
+    front: Vec<(&'a Node<K, V>, usize)>,
+    /// Nodes on the path to the next tuple from the back
+    back: Vec<(&'a Node<K, V>, usize)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for PersistentTuples<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let tuple = current(&self.front);
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            step(&mut self.front, true);
+        }
+        Some(tuple)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for PersistentTuples<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let tuple = current(&self.back);
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            step(&mut self.back, false);
+        }
+        Some(tuple)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for PersistentTuples<'a, K, V> {}
+
+impl<'a, K, V> fmt::Debug for PersistentTuples<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "PersistentTuples {{ remaining: {} }}", self.remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::{Node, PersistentSortedList};
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    fn to_vec<'a, I: Iterator<Item = (&'a u32, &'a u32)>>(it: I) -> Vec<(u32, u32)> {
+        it.map(|(a, b)| (*a, *b)).collect()
+    }
+
+    #[test]
+    fn matches_sorted_list() {
+        let mut expected = SortedList::new();
+        let mut list = PersistentSortedList::new();
+
+        for i in 0..5000u32 {
+            let key = (i * 7919) % 1013;
+            expected.insert(key, i % 3);
+            list = list.insert(key, i % 3);
+        }
+
+        assert_eq!(list.len(), expected.len());
+        assert_eq!(to_vec(list.iter()), to_vec(expected.iter()));
+        assert_eq!(
+            to_vec(list.range(100..200)),
+            to_vec(expected.range(100..200))
+        );
+        assert_eq!(to_vec(list.range(..=500)), to_vec(expected.range(..=500)));
+        assert_eq!(to_vec(list.range(1000..)), to_vec(expected.range(1000..)));
+        assert!(list.values_of(&42).eq(expected.values_of(&42)));
+        assert_eq!(list.first(), expected.first());
+        assert_eq!(list.last(), expected.last());
+    }
+
+    #[test]
+    fn old_versions_are_unchanged() {
+        let mut versions = vec![PersistentSortedList::new()];
+        for i in 0..200u32 {
+            let next = versions.last().unwrap().insert(i % 17, i);
+            versions.push(next);
+        }
+
+        for (n, version) in versions.iter().enumerate() {
+            assert_eq!(version.len(), n);
+            assert_eq!(version.iter().count(), n);
+        }
+
+        let removed = versions[200].remove(&3, &3);
+        assert_eq!(removed.len(), 199);
+        assert_eq!(versions[200].first_value_of(&3), Some(&3));
+        assert_eq!(removed.first_value_of(&3), Some(&20));
+    }
+
+    #[test]
+    fn insert_shares_untouched_nodes() {
+        let list = (0..10_000u32)
+            .map(|k| (k, k))
+            .collect::<PersistentSortedList<_, _>>();
+        let next = list.insert(0, 1);
+
+        match (
+            &**list.root.as_ref().unwrap(),
+            &**next.root.as_ref().unwrap(),
+        ) {
+            (Node::Branch(before), Node::Branch(after)) => {
+                assert!(!Arc::ptr_eq(&before[0].node, &after[0].node));
+                assert!(before[1..]
+                    .iter()
+                    .zip(after[1..].iter())
+                    .all(|(a, b)| Arc::ptr_eq(&a.node, &b.node)));
+            }
+            _ => panic!("expected branches at the root"),
+        }
+    }
+
+    #[test]
+    fn duplicates_and_missing() {
+        let list = PersistentSortedList::new().insert(1u32, 1u32);
+        assert_eq!(list.insert(1, 1).len(), 1);
+        assert_eq!(list.remove(&1, &2).len(), 1);
+        assert_eq!(list.remove(&2, &1).len(), 1);
+        assert!(list.remove(&1, &1).is_empty());
+        assert!(list.contains(&1, &1));
+    }
+
+    #[test]
+    fn remove_everything() {
+        let mut list = (0..2000u32)
+            .map(|k| (k / 2, k))
+            .collect::<PersistentSortedList<_, _>>();
+
+        for k in (0..2000u32).rev() {
+            if k % 2 == 0 {
+                list = list.remove(&(k / 2), &k);
+            }
+        }
+        assert_eq!(list.len(), 1000);
+        assert_eq!(to_vec(list.range(10..12)), vec![(10, 21), (11, 23)]);
+
+        for k in 0..2000u32 {
+            list = list.remove(&(k / 2), &k);
+        }
+        assert!(list.is_empty());
+        assert_eq!(list.iter().next(), None);
+        assert_eq!(list.first(), None);
+    }
+
+    #[test]
+    fn hot_key_shares_untouched_nodes() {
+        let list = (0..2000u32)
+            .map(|v| (0, v))
+            .collect::<PersistentSortedList<_, _>>();
+        let next = list.insert(0, 2000);
+
+        assert_eq!(next.len(), 2001);
+        assert_eq!(next.last_value_of(&0), Some(&2000));
+        match (
+            &**list.root.as_ref().unwrap(),
+            &**next.root.as_ref().unwrap(),
+        ) {
+            (Node::Branch(before), Node::Branch(after)) => {
+                let last = before.len() - 1;
+                assert!(before[..last]
+                    .iter()
+                    .zip(after.iter())
+                    .all(|(a, b)| Arc::ptr_eq(&a.node, &b.node)));
+            }
+            _ => panic!("expected branches at the root"),
+        }
+    }
+
+    #[test]
+    fn value_orders_match_sorted_list() {
+        let lists = vec![
+            (
+                SortedList::sorted_values(),
+                PersistentSortedList::sorted_values(),
+            ),
+            (
+                SortedList::most_recent_first(),
+                PersistentSortedList::most_recent_first(),
+            ),
+        ];
+
+        for (mut expected, mut list) in lists {
+            for i in 0..3000u32 {
+                let (key, value) = (i % 7, (i * 7919) % 500);
+                expected.insert(key, value);
+                list = list.insert(key, value);
+            }
+            for i in 0..1000u32 {
+                let (key, value) = (i % 5, (i * 104_729) % 500);
+                expected.remove(&key, &value);
+                list = list.remove(&key, &value);
+            }
+
+            assert_eq!(list.value_order(), expected.value_order());
+            assert_eq!(list.len(), expected.len());
+            assert_eq!(to_vec(list.iter()), to_vec(expected.iter()));
+            for key in 0..8 {
+                assert_eq!(list.contains_key(&key), expected.contains_key(&key));
+                assert_eq!(list.first_value_of(&key), expected.first_value_of(&key));
+                assert_eq!(list.last_value_of(&key), expected.last_value_of(&key));
+            }
+        }
+    }
+
+    #[test]
+    fn double_ended_and_exact_size() {
+        let list = (0..1000u32)
+            .map(|k| (k / 3, k))
+            .collect::<PersistentSortedList<_, _>>();
+        let expected = list.iter().collect::<Vec<_>>();
+
+        let mut tuples = list.range(10..300);
+        assert_eq!(tuples.len(), 870);
+        assert_eq!(tuples.next_back(), Some((&299, &899)));
+        assert_eq!(tuples.next(), Some((&10, &30)));
+        assert_eq!(tuples.len(), 868);
+
+        let mut both = Vec::new();
+        while let Some(front) = tuples.next() {
+            both.push(front);
+            if let Some(back) = tuples.next_back() {
+                both.push(back);
+            }
+        }
+        assert_eq!(both.len(), 868);
+        both.sort();
+        assert_eq!(both, &expected[31..899]);
+
+        assert_eq!(
+            list.values_of(&5).rev().collect::<Vec<_>>(),
+            vec![&17, &16, &15]
+        );
+        assert_eq!(list.values_of(&5000).len(), 0);
+        assert_eq!(
+            list.range((Bound::Included(40), Bound::Excluded(20))).len(),
+            0
+        );
+        assert_eq!(list.partition_point(|k| *k < 100), 300);
+    }
+}
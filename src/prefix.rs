@@ -0,0 +1,256 @@
+//! Prefix searches over string keys.
+
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::{SortedList, Tuples};
+
+/// Key which is ordered like its string form, allowing prefix searches on a `SortedList`.
+///
+/// Implementations must order the keys exactly like `key_str` would be ordered, otherwise the
+/// prefix searches return wrong results.
+pub trait StrKey: Ord {
+    /// Returns the key as a string
+    fn key_str(&self) -> &str;
+}
+
+impl StrKey for String {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl StrKey for &str {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl StrKey for Box<str> {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl StrKey for Rc<str> {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl StrKey for Arc<str> {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl<'a> StrKey for Cow<'a, str> {
+    fn key_str(&self) -> &str {
+        self
+    }
+}
+
+impl<K: StrKey, V: PartialEq> SortedList<K, V> {
+    /// Returns an iterator over the tuples with a key starting with `prefix`.
+    ///
+    /// The keys with a common prefix are next to each other, so this takes two binary searches
+    /// without computing an upper bound for the prefix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let list = vec![("apple", 1), ("apricot", 2), ("äpple", 3), ("banana", 4)]
+    ///     .into_iter()
+    ///     .collect::<SortedList<_, _>>();
+    ///
+    /// assert_eq!(
+    ///     list.prefix("ap").map(|(k, _)| *k).collect::<Vec<_>>(),
+    ///     vec!["apple", "apricot"]);
+    /// assert_eq!(list.prefix("ä").count(), 1);
+    /// ```
+    pub fn prefix(&self, prefix: &str) -> Tuples<'_, K, V> {
+        let (low, high) = self.prefix_range(prefix);
+
+        Tuples {
+            keys: self.key_slice(),
+            values: self.value_slice(),
+            low,
+            high,
+        }
+    }
+
+    /// Returns the first key sharing the longest common prefix with `query`, together with the
+    /// length of the prefix in bytes. The length is always at a `char` boundary of `query`, so
+    /// `prefix(&query[..len])` returns all of the keys sharing it.
+    ///
+    /// Returns `None` if no key has even the first character in common with `query`.
+    pub fn longest_common_prefix_match(&self, query: &str) -> Option<(&K, usize)> {
+        let keys = self.key_slice();
+        let at = keys.partition_point(|k| k.key_str() < query);
+
+        // the keys closest to `query` share the longest prefix with it
+        let before = at
+            .checked_sub(1)
+            .map(|i| common_prefix_len(keys[i].key_str(), query));
+        let after = keys.get(at).map(|k| common_prefix_len(k.key_str(), query));
+
+        let len = before.into_iter().chain(after).max().unwrap_or(0);
+        if len == 0 {
+            return None;
+        }
+
+        let (first, _) = self.prefix_range(&query[..len]);
+        Some((&keys[first], len))
+    }
+
+    /// Returns the first `n` distinct keys starting with `prefix` in order
+    pub fn complete(&self, prefix: &str, n: usize) -> Vec<&K> {
+        let (low, high) = self.prefix_range(prefix);
+        self.groups(low, high).take(n).map(|(key, _)| key).collect()
+    }
+
+    /// Returns the `n` distinct keys starting with `prefix` with the highest score, computed from
+    /// the key and all of its values. Keys with equal scores are returned in order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::SortedList;
+    ///
+    /// let searches = vec![("rust", 1), ("rye", 2), ("rust", 3), ("ruby", 4), ("rust", 5)]
+    ///     .into_iter()
+    ///     .collect::<SortedList<_, _>>();
+    ///
+    /// // the most searched for first
+    /// assert_eq!(
+    ///     searches.complete_by("r", 2, |_, values| values.len()),
+    ///     vec![&"rust", &"ruby"]);
+    /// ```
+    pub fn complete_by<F, S>(&self, prefix: &str, n: usize, mut score: F) -> Vec<&K>
+    where
+        F: FnMut(&K, &[V]) -> S,
+        S: Ord,
+    {
+        let (low, high) = self.prefix_range(prefix);
+        let mut scored = self
+            .groups(low, high)
+            .map(|(key, values)| (score(key, values), key))
+            .collect::<Vec<_>>();
+
+        // stable, so that equal scores stay in key order
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.into_iter().take(n).map(|(_, key)| key).collect()
+    }
+
+    fn prefix_range(&self, prefix: &str) -> (usize, usize) {
+        let keys = self.key_slice();
+        let low = keys.partition_point(|k| k.key_str() < prefix);
+        let high = low + keys[low..].partition_point(|k| k.key_str().starts_with(prefix));
+        (low, high)
+    }
+
+    /// Returns every distinct key in `low..high` with all of its values
+    fn groups(&self, mut low: usize, high: usize) -> impl Iterator<Item = (&K, &[V])> {
+        let keys = self.key_slice();
+        let values = self.value_slice();
+
+        ::std::iter::from_fn(move || {
+            if low == high {
+                return None;
+            }
+
+            let key = &keys[low];
+            let end = low + keys[low..high].partition_point(|k| k == key);
+            let group = (key, &values[low..end]);
+            low = end;
+            Some(group)
+        })
+    }
+}
+
+/// Returns the length in bytes of the common prefix of the strings
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|&((_, x), y)| x != y)
+        .map(|((at, _), _)| at)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+
+    fn sample() -> SortedList<String, u32> {
+        [
+            "car", "card", "care", "careful", "cart", "cat", "dog", "ça", "çava",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, k)| (k.to_string(), i as u32))
+        .collect()
+    }
+
+    fn keys<'a, I: Iterator<Item = (&'a String, &'a u32)>>(iter: I) -> Vec<&'a str> {
+        iter.map(|(k, _)| k.as_str()).collect()
+    }
+
+    #[test]
+    fn prefix() {
+        let list = sample();
+
+        assert_eq!(
+            keys(list.prefix("car")),
+            vec!["car", "card", "care", "careful", "cart"]
+        );
+        assert_eq!(keys(list.prefix("care")), vec!["care", "careful"]);
+        assert_eq!(keys(list.prefix("ç")), vec!["ça", "çava"]);
+        assert_eq!(list.prefix("").count(), list.len());
+        assert_eq!(list.prefix("x").count(), 0);
+        assert_eq!(list.prefix("carefully").count(), 0);
+    }
+
+    #[test]
+    fn longest_common_prefix_match() {
+        let list = sample();
+
+        let lcp = |query: &str| {
+            list.longest_common_prefix_match(query)
+                .map(|(k, len)| (k.as_str(), len))
+        };
+
+        assert_eq!(lcp("carpet"), Some(("car", 3)));
+        assert_eq!(lcp("careless"), Some(("care", 4)));
+        assert_eq!(lcp("cat"), Some(("cat", 3)));
+        assert_eq!(lcp("cab"), Some(("car", 2)));
+        assert_eq!(lcp("çb"), Some(("ça", "ç".len())));
+        assert_eq!(lcp("cé"), Some(("car", 1)));
+        assert_eq!(lcp("x"), None);
+        assert_eq!(
+            SortedList::<String, u32>::new().longest_common_prefix_match("a"),
+            None
+        );
+    }
+
+    #[test]
+    fn complete() {
+        let mut list = sample();
+        list.insert("care".to_string(), 100);
+        list.insert("cart".to_string(), 101);
+        list.insert("cart".to_string(), 102);
+
+        assert_eq!(list.complete("car", 3), vec!["car", "card", "care"]);
+        assert_eq!(list.complete("ca", 100).len(), 6);
+        assert_eq!(
+            list.complete_by("car", 2, |_, values| values.len()),
+            vec!["cart", "care"]
+        );
+        assert_eq!(
+            list.complete_by("car", 10, |k, _| k.len()),
+            vec!["careful", "card", "care", "cart", "car"]
+        );
+    }
+}
@@ -0,0 +1,593 @@
+//! Strategies for searching the keys of a `SortedList`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use super::SortedList;
+
+/// How a `SortedList` searches its keys, chosen with `SortedList::with_search`.
+///
+/// Lists use `slice::binary_search` unless constructed with another strategy. Strategies which
+/// keep a model or a copy of the keys are told about every change through `changed`, and can be
+/// brought up to date at once through `rebuild`.
+pub trait SearchStrategy<K>: Send + Sync {
+    /// Searches `key` in the sorted `keys` like `slice::binary_search`: returns `Ok` with the index
+    /// of any equal key, or `Err` with the index where `key` would be inserted.
+    fn search(&self, keys: &[K], key: &K) -> Result<usize, usize>;
+
+    /// Called after the keys of the list changed, with the keys as they are now
+    fn changed(&mut self, keys: &[K]) {
+        let _ = keys;
+    }
+
+    /// Called by `SortedList::rebuild_search` and `SortedList::with_search` to bring any copy or
+    /// model of the keys up to date with `keys` right away
+    fn rebuild(&mut self, keys: &[K]) {
+        let _ = keys;
+    }
+
+    /// Clones the strategy for a cloned or split list
+    fn box_clone(&self) -> Box<dyn SearchStrategy<K>>;
+}
+
+/// Key which can be placed on a number line, allowing the searches to guess where it is.
+///
+/// Implemented for the integer types. Floats are not `Ord`, but a wrapper which orders them can
+/// implement this trait.
+pub trait NumericKey: Ord {
+    /// Returns the key as a float, which must grow with the key
+    fn to_f64(&self) -> f64;
+}
+
+macro_rules! numeric_key {
+    ($($t:ty),*) => {
+        $(
+            impl NumericKey for $t {
+                fn to_f64(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    };
+}
+
+numeric_key!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// Plain binary search, the same as a list constructed without a strategy
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BinarySearch;
+
+impl<K: Ord> SearchStrategy<K> for BinarySearch {
+    fn search(&self, keys: &[K], key: &K) -> Result<usize, usize> {
+        keys.binary_search(key)
+    }
+
+    fn box_clone(&self) -> Box<dyn SearchStrategy<K>> {
+        Box::new(*self)
+    }
+}
+
+/// Interpolation search for evenly spread numeric keys, guessing the position of the key from
+/// the first and last keys of the remaining range. Takes `O(log log n)` steps on evenly spread
+/// keys and falls back to binary search after a few guesses on skewed ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InterpolationSearch;
+
+/// Number of guesses before `InterpolationSearch` falls back to binary search
+const MAX_INTERPOLATIONS: usize = 4;
+
+impl<K: NumericKey> SearchStrategy<K> for InterpolationSearch {
+    fn search(&self, keys: &[K], key: &K) -> Result<usize, usize> {
+        // the keys before `low` are smaller than `key`, the ones from `high` on are not
+        let mut low = 0;
+        let mut high = keys.len();
+
+        for _ in 0..MAX_INTERPOLATIONS {
+            if high - low <= 8 {
+                break;
+            }
+
+            if *key <= keys[low] {
+                high = low;
+                break;
+            }
+            if keys[high - 1] < *key {
+                low = high;
+                break;
+            }
+
+            let first = keys[low].to_f64();
+            let last = keys[high - 1].to_f64();
+            let fraction = (key.to_f64() - first) / (last - first);
+            let guess = low + (fraction * (high - 1 - low) as f64) as usize;
+            let guess = guess.clamp(low, high - 1);
+
+            if keys[guess] < *key {
+                low = guess + 1;
+            } else {
+                high = guess;
+            }
+        }
+
+        found_at(
+            keys,
+            key,
+            low + keys[low..high].partition_point(|k| k < key),
+        )
+    }
+
+    fn box_clone(&self) -> Box<dyn SearchStrategy<K>> {
+        Box::new(*self)
+    }
+}
+
+/// Binary search over a copy of the keys in Eytzinger order, where the keys compared first are
+/// next to each other in memory, which makes the search friendlier to the caches on large lists.
+///
+/// Every change drops the copy and the keys of the list are binary searched until a sixteenth as
+/// many searches as there are keys have been made without changes in between, which then build
+/// the copy again. This suits lists which are filled first and then mostly read, and
+/// `SortedList::rebuild_search` builds the copy right away once the list has been filled.
+#[derive(Debug)]
+pub struct EytzingerSearch<K> {
+    // every key with its position in the list, which share a cache line
+    layout: OnceLock<Vec<(K, usize)>>,
+    // searches made without the copy since the last change
+    misses: AtomicUsize,
+}
+
+impl<K> EytzingerSearch<K> {
+    /// Creates the strategy, which builds the copy once the list has keys
+    pub fn new() -> Self {
+        EytzingerSearch {
+            layout: OnceLock::new(),
+            misses: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<K: Clone> EytzingerSearch<K> {
+    fn build(keys: &[K]) -> Vec<(K, usize)> {
+        eytzinger_positions(keys.len())
+            .into_iter()
+            .map(|at| (keys[at].clone(), at))
+            .collect()
+    }
+}
+
+impl<K> Default for EytzingerSearch<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone> Clone for EytzingerSearch<K> {
+    fn clone(&self) -> Self {
+        EytzingerSearch {
+            layout: self.layout.clone(),
+            misses: AtomicUsize::new(self.misses.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<K: Ord + Clone + Send + Sync + 'static> SearchStrategy<K> for EytzingerSearch<K> {
+    fn search(&self, keys: &[K], key: &K) -> Result<usize, usize> {
+        let layout = match self.layout.get() {
+            Some(layout) => layout,
+            None if self.misses.fetch_add(1, Ordering::Relaxed) < keys.len() / 16 => {
+                return keys.binary_search(key);
+            }
+            None => self.layout.get_or_init(|| Self::build(keys)),
+        };
+
+        match eytzinger_lower_bound(layout, |(k, _)| k < key) {
+            Some(node) if layout[node].0 == *key => Ok(layout[node].1),
+            Some(node) => Err(layout[node].1),
+            None => Err(keys.len()),
+        }
+    }
+
+    fn changed(&mut self, _keys: &[K]) {
+        self.layout.take();
+        *self.misses.get_mut() = 0;
+    }
+
+    fn rebuild(&mut self, keys: &[K]) {
+        self.layout = OnceLock::from(Self::build(keys));
+    }
+
+    fn box_clone(&self) -> Box<dyn SearchStrategy<K>> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns the sorted position stored at every node of an Eytzinger layout of `len` keys, where
+/// the children of node `n` are `2n + 1` and `2n + 2`
+fn eytzinger_positions(len: usize) -> Vec<usize> {
+    fn fill(positions: &mut [usize], node: usize, next: &mut usize) {
+        if node < positions.len() {
+            fill(positions, 2 * node + 1, next);
+            positions[node] = *next;
+            *next += 1;
+            fill(positions, 2 * node + 2, next);
+        }
+    }
+
+    let mut positions = vec![0; len];
+    fill(&mut positions, 0, &mut 0);
+    positions
+}
+
+/// Returns the node of the first element of an Eytzinger `layout` for which `less` returns
+/// `false`, like `slice::partition_point` does for sorted slices
+fn eytzinger_lower_bound<T, F>(layout: &[T], mut less: F) -> Option<usize>
+where
+    F: FnMut(&T) -> bool,
+{
+    // walk down without branching on the comparisons, numbering the nodes from one so that the
+    // path taken can be read from the bits: going right appends a one, going left a zero
+    let mut node = 1;
+    while node <= layout.len() {
+        node = 2 * node + less(&layout[node - 1]) as usize;
+    }
+
+    // the answer is where the path last went left
+    let node = node >> (node.trailing_ones() + 1);
+    node.checked_sub(1)
+}
+
+/// Piecewise linear model of the keys of the list, predicting the position of a key within a few
+/// places, which are then searched.
+///
+/// The model is fitted again after the keys have changed by an eighth, or by
+/// `SortedList::rebuild_search`. Until then the predictions can be further off, which makes the
+/// searches slower but not wrong.
+#[derive(Debug, Clone, Default)]
+pub struct LearnedIndex {
+    segments: Vec<Segment>,
+    changes: usize,
+}
+
+/// Line predicting the positions of the keys from `key` until the next segment
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    key: f64,
+    position: usize,
+    slope: f64,
+}
+
+/// Largest distance between a predicted and an actual position the model is fitted to
+const MAX_ERROR: f64 = 16.0;
+
+impl LearnedIndex {
+    /// Creates the strategy, which fits the model once the list has keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of linear segments in the model
+    pub fn segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn predict(&self, key: f64) -> usize {
+        let at = self.segments.partition_point(|s| s.key <= key);
+        match self.segments.get(at.saturating_sub(1)) {
+            Some(s) => (s.position as f64 + (key - s.key) * s.slope).max(0.0) as usize,
+            None => 0,
+        }
+    }
+
+    /// Fits the segments to the first position of every distinct key, starting a new segment when
+    /// no line from its first key stays within `MAX_ERROR` of all of its keys
+    fn fit<K: NumericKey>(&mut self, keys: &[K]) {
+        self.segments.clear();
+
+        let mut start = Segment {
+            key: 0.0,
+            position: 0,
+            slope: 0.0,
+        };
+        let (mut low, mut high) = (0.0f64, f64::INFINITY);
+
+        for (i, key) in keys.iter().enumerate() {
+            if i > 0 && keys[i - 1] == *key {
+                continue;
+            }
+
+            let x = key.to_f64();
+            if i == 0 {
+                start.key = x;
+                continue;
+            }
+
+            let dx = x - start.key;
+            let dy = (i - start.position) as f64;
+            let (l, h) = if dx > 0.0 {
+                ((dy - MAX_ERROR) / dx, (dy + MAX_ERROR) / dx)
+            } else if dy <= MAX_ERROR {
+                // keys too close to tell apart as floats
+                (low, high)
+            } else {
+                (f64::INFINITY, f64::NEG_INFINITY)
+            };
+
+            if l.max(low) <= h.min(high) {
+                low = l.max(low);
+                high = h.min(high);
+                continue;
+            }
+
+            start.slope = segment_slope(low, high);
+            self.segments.push(start);
+            start = Segment {
+                key: x,
+                position: i,
+                slope: 0.0,
+            };
+            low = 0.0;
+            high = f64::INFINITY;
+        }
+
+        if !keys.is_empty() {
+            start.slope = segment_slope(low, high);
+            self.segments.push(start);
+        }
+    }
+}
+
+fn segment_slope(low: f64, high: f64) -> f64 {
+    if high.is_finite() {
+        (low + high) / 2.0
+    } else {
+        low
+    }
+}
+
+impl<K: NumericKey> SearchStrategy<K> for LearnedIndex {
+    fn search(&self, keys: &[K], key: &K) -> Result<usize, usize> {
+        let guess = self.predict(key.to_f64());
+        found_at(keys, key, gallop(keys, key, guess))
+    }
+
+    fn changed(&mut self, keys: &[K]) {
+        self.changes += 1;
+        if self.changes > keys.len() / 8 {
+            self.rebuild(keys);
+        }
+    }
+
+    fn rebuild(&mut self, keys: &[K]) {
+        self.fit(keys);
+        self.changes = 0;
+    }
+
+    fn box_clone(&self) -> Box<dyn SearchStrategy<K>> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns the position of the first key not less than `key`, searching outwards from `guess` in
+/// growing steps, so that a guess `d` places off takes `O(log d)` comparisons
+fn gallop<K: Ord>(keys: &[K], key: &K, guess: usize) -> usize {
+    let guess = guess.min(keys.len());
+
+    let (low, high) = if guess < keys.len() && keys[guess] < *key {
+        let mut low = guess + 1;
+        let mut step = 1;
+        loop {
+            let probe = low + step - 1;
+            if probe >= keys.len() {
+                break (low, keys.len());
+            }
+            if keys[probe] < *key {
+                low = probe + 1;
+                step *= 2;
+            } else {
+                break (low, probe);
+            }
+        }
+    } else {
+        let mut high = guess;
+        let mut step = 1;
+        loop {
+            if high < step {
+                break (0, high);
+            }
+            let probe = high - step;
+            if keys[probe] < *key {
+                break (probe + 1, high);
+            }
+            high = probe;
+            step *= 2;
+        }
+    };
+
+    low + keys[low..high].partition_point(|k| k < key)
+}
+
+/// Turns the position of the first key not less than `key` into a `slice::binary_search` result
+fn found_at<K: Ord>(keys: &[K], key: &K, at: usize) -> Result<usize, usize> {
+    match keys.get(at) {
+        Some(k) if k == key => Ok(at),
+        _ => Err(at),
+    }
+}
+
+impl<K: Ord, V: PartialEq> SortedList<K, V> {
+    /// Makes the list search its keys with `search` instead of `slice::binary_search`. Can be
+    /// chained after any of the constructors, so that it combines with every `ValueOrder`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sorted_list::{LearnedIndex, SortedList, ValueOrder};
+    ///
+    /// let mut list = SortedList::sorted_values().with_search(LearnedIndex::new());
+    /// for i in 0..1000u64 {
+    ///     list.insert(i * 3, 1000 - i);
+    ///     list.insert(i * 3, i);
+    /// }
+    ///
+    /// assert_eq!(list.value_order(), ValueOrder::Sorted);
+    /// assert_eq!(list.values_of(&300), &[100, 900]);
+    /// assert_eq!(list.values_of(&301).len(), 0);
+    /// ```
+    pub fn with_search<S: SearchStrategy<K> + 'static>(mut self, search: S) -> Self {
+        self.search = Some(Box::new(search));
+        self.rebuild_search();
+        self
+    }
+
+    /// Brings the `SearchStrategy` of the list up to date with its keys right away, instead of
+    /// waiting for it to catch up with the changes. Useful after filling a list which is then
+    /// mostly read.
+    pub fn rebuild_search(&mut self) {
+        if let Some(ref mut search) = self.search {
+            let (keys, rest) = self.keys.as_slices();
+            debug_assert!(rest.is_empty());
+            search.rebuild(keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use super::{
+        gallop, BinarySearch, EytzingerSearch, InterpolationSearch, LearnedIndex, SearchStrategy,
+    };
+
+    fn skewed_keys() -> Vec<u64> {
+        let mut keys = (0..2000u64).map(|i| i * i / 7).collect::<Vec<_>>();
+        keys.extend(vec![5_000_000; 20]);
+        keys.push(u64::MAX);
+        keys.sort();
+        keys
+    }
+
+    fn agrees_with_binary_search<S: SearchStrategy<u64>>(mut search: S) {
+        let keys = skewed_keys();
+        for i in 0..keys.len() {
+            search.changed(&keys[..i]);
+        }
+        search.changed(&keys);
+
+        for key in (0..600_000u64).step_by(97).chain(vec![5_000_000, u64::MAX]) {
+            match search.search(&keys, &key) {
+                Ok(at) => assert_eq!(keys[at], key),
+                Err(at) => {
+                    assert!(keys.binary_search(&key).is_err(), "key {}", key);
+                    assert_eq!(at, keys.partition_point(|k| *k < key), "key {}", key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strategies_agree_with_binary_search() {
+        agrees_with_binary_search(BinarySearch);
+        agrees_with_binary_search(InterpolationSearch);
+        agrees_with_binary_search(EytzingerSearch::new());
+        agrees_with_binary_search(LearnedIndex::new());
+    }
+
+    #[test]
+    fn gallop_from_any_guess() {
+        let keys = vec![1, 3, 3, 3, 5, 8, 8, 13];
+        for key in 0..15 {
+            let expected = keys.partition_point(|k| *k < key);
+            for guess in 0..12 {
+                assert_eq!(gallop(&keys, &key, guess), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn lists_keep_working_through_changes() {
+        let mut lists = vec![
+            SortedList::new().with_search(InterpolationSearch),
+            SortedList::new().with_search(EytzingerSearch::new()),
+            SortedList::new().with_search(LearnedIndex::new()),
+        ];
+
+        for list in &mut lists {
+            for i in 0..500u32 {
+                list.insert((i * 7919) % 1000, i);
+            }
+            for i in 0..100u32 {
+                list.remove(&((i * 7919) % 1000), &i);
+            }
+            list.pop_first();
+            let mut tail = list.split_off(&900);
+            tail.insert(950, 0);
+            assert_eq!(tail.values_of(&950).len(), 1);
+
+            let copy = list.clone();
+            for key in 0..1000 {
+                assert_eq!(copy.values_of(&key), list.values_of(&key));
+            }
+        }
+
+        let expected = lists[0].iter().collect::<Vec<_>>();
+        for list in &lists[1..] {
+            assert_eq!(list.iter().collect::<Vec<_>>(), expected);
+            for key in 0..1000 {
+                assert_eq!(list.values_of(&key), lists[0].values_of(&key));
+            }
+        }
+    }
+
+    #[test]
+    fn learned_index_fits_few_segments() {
+        let keys = (0..10_000u64).map(|i| i * 10).collect::<Vec<_>>();
+        let mut index = LearnedIndex::new();
+        index.fit(&keys);
+        assert_eq!(index.segments(), 1);
+
+        let keys = skewed_keys();
+        index.fit(&keys);
+        assert!(index.segments() < keys.len() / 8);
+    }
+
+    #[test]
+    fn eytzinger_layout_is_used_once_reading() {
+        let keys = skewed_keys();
+        let mut search = EytzingerSearch::new();
+        for i in 0..keys.len() {
+            search.changed(&keys[..i]);
+        }
+        search.changed(&keys);
+        assert!(search.layout.get().is_none());
+
+        for key in 0..(keys.len() / 16) as u64 {
+            let _ = search.search(&keys, &key);
+        }
+        assert!(search.layout.get().is_none());
+        assert_eq!(search.search(&keys, &u64::MAX), Ok(keys.len() - 1));
+        assert!(search.layout.get().is_some());
+
+        search.changed(&keys[1..]);
+        assert!(search.layout.get().is_none());
+        search.rebuild(&keys[1..]);
+        assert_eq!(search.layout.get().map(Vec::len), Some(keys.len() - 1));
+    }
+
+    #[test]
+    fn search_combines_with_value_orders() {
+        let mut list = SortedList::most_recent_first().with_search(EytzingerSearch::new());
+        for i in 0..300u32 {
+            list.insert(i % 30, i);
+        }
+        list.rebuild_search();
+
+        assert_eq!(list.values_of(&7).first(), Some(&277));
+        assert_eq!(list.values_of(&7).len(), 10);
+
+        let list = (0..100u32)
+            .map(|i| (i % 10, 100 - i))
+            .collect::<SortedList<_, _>>()
+            .with_search(InterpolationSearch);
+        assert_eq!(list.values_of(&3).len(), 10);
+    }
+}
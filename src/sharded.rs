@@ -0,0 +1,399 @@
+//! `SortedList` partitioned into independently locked key ranges.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{IntoTuples, SortedList};
+
+/// Shards are not rebalanced before one holds this many tuples over its fair share, so that small
+/// lists do not keep rebalancing.
+const REBALANCE_SLACK: usize = 64;
+
+/// A `SortedList` split into key range shards, each behind its own lock, so that writers to
+/// different key ranges do not wait for each other.
+///
+/// The shard boundaries are moved whenever one of the shards grows to more than one and a half
+/// times its fair share of the tuples. All values of a key always live in the same shard, so a
+/// shard holding a large key is only rebalanced again once it grows to one and a half times the
+/// size of the largest key seen by the last rebalance.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::ShardedSortedList;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let list = Arc::new(ShardedSortedList::new(4));
+///
+/// let handles = (0..4u32)
+///     .map(|t| {
+///         let list = Arc::clone(&list);
+///         thread::spawn(move || {
+///             for i in 0..100u32 {
+///                 list.insert(i * 4 + t, t);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+///
+/// assert_eq!(list.len(), 400);
+/// assert_eq!(list.range(10..14).collect::<Vec<_>>(), vec![(10, 2), (11, 3), (12, 0), (13, 1)]);
+/// ```
+pub struct ShardedSortedList<K: Ord, V: PartialEq> {
+    /// Shard `i` holds the keys in `boundaries[i - 1]..boundaries[i]`. There can be less than
+    /// `shards.len() - 1` boundaries in which case the shards at the end are empty.
+    boundaries: RwLock<Vec<K>>,
+    shards: Vec<RwLock<SortedList<K, V>>>,
+    len: AtomicUsize,
+    /// Number of values of the largest key at the last rebalance, which cannot be split
+    largest_group: AtomicUsize,
+}
+
+impl<K: Ord + Clone, V: PartialEq + Clone> ShardedSortedList<K, V> {
+    /// Creates a new empty list with `shards` shards
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn new(shards: usize) -> Self {
+        assert!(shards > 0, "at least one shard is required");
+
+        ShardedSortedList {
+            boundaries: RwLock::new(Vec::new()),
+            shards: (0..shards)
+                .map(|_| RwLock::new(SortedList::new()))
+                .collect(),
+            len: AtomicUsize::new(0),
+            largest_group: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of tuples
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the collection is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of tuples in each of the shards
+    pub fn shard_lens(&self) -> Vec<usize> {
+        let _boundaries = self.read_boundaries();
+        self.shards.iter().map(|shard| read(shard).len()).collect()
+    }
+
+    /// Inserts the tuple locking only the shard owning `key`, see `SortedList::insert`. Might
+    /// rebalance the shards afterwards, which locks all of them.
+    pub fn insert(&self, key: K, value: V) -> bool {
+        let needs_rebalance = {
+            let boundaries = self.read_boundaries();
+            let index = shard_of(&boundaries, &key);
+            let mut shard = write(&self.shards[index]);
+
+            if !shard.insert(key, value) {
+                return false;
+            }
+
+            let len = self.len.fetch_add(1, Ordering::SeqCst) + 1;
+            self.needs_rebalance(shard.len(), len)
+        };
+
+        if needs_rebalance {
+            self.rebalance();
+        }
+
+        true
+    }
+
+    /// Returns `true` if a shard of `shard_len` tuples in a list of `len` tuples is too large.
+    /// A shard cannot shrink below its largest key, so it is measured against that too. The bound
+    /// stays below `len` for two shards, whose fair share is half of the list.
+    fn needs_rebalance(&self, shard_len: usize, len: usize) -> bool {
+        let fair = len / self.shards.len();
+        let largest_group = self.largest_group.load(Ordering::SeqCst);
+        shard_len > fair.max(largest_group) * 3 / 2 + REBALANCE_SLACK
+    }
+
+    /// Returns the values of `key`, see `SortedList::values_of`
+    pub fn values_of(&self, key: &K) -> Vec<V> {
+        let boundaries = self.read_boundaries();
+        let shard = read(&self.shards[shard_of(&boundaries, key)]);
+        shard.values_of(key).to_vec()
+    }
+
+    /// Returns the tuples in the specified range in order across all shards. The shards
+    /// overlapping the range are locked together, so the result is consistent.
+    pub fn range<R>(&self, range: R) -> IntoTuples<K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        use std::ops::Bound::*;
+
+        let boundaries = self.read_boundaries();
+
+        let first = match range.start_bound() {
+            Included(key) | Excluded(key) => shard_of(&boundaries, key),
+            Unbounded => 0,
+        };
+
+        let last = match range.end_bound() {
+            Included(key) | Excluded(key) => shard_of(&boundaries, key),
+            Unbounded => self.shards.len() - 1,
+        };
+
+        let guards = self.shards[first..=last.max(first)]
+            .iter()
+            .map(read)
+            .collect::<Vec<_>>();
+
+        let mut keys = VecDeque::new();
+        let mut values = VecDeque::new();
+
+        for shard in &guards {
+            for (k, v) in shard.range((range.start_bound(), range.end_bound())) {
+                keys.push_back(k.clone());
+                values.push_back(v.clone());
+            }
+        }
+
+        IntoTuples {
+            keys: keys.into_iter(),
+            values: values.into_iter(),
+        }
+    }
+
+    /// Returns all of the tuples in order across all shards
+    pub fn iter(&self) -> IntoTuples<K, V> {
+        self.range(..)
+    }
+
+    /// Moves the shard boundaries so that every shard holds about the same number of tuples.
+    /// Locks all of the shards while doing so.
+    pub fn rebalance(&self) {
+        let mut boundaries = write(&self.boundaries);
+        let mut guards = self.shards.iter().map(write).collect::<Vec<_>>();
+
+        let mut all = SortedList::new();
+        for shard in &mut guards {
+            // shards are in key order, so their tuples can be appended as is
+            for (k, v) in mem::take(&mut **shard) {
+                all.push_unchecked(k, v);
+            }
+        }
+
+        let mut largest_group = 0;
+        let mut start = 0;
+        while start < all.len() {
+            let end = all.find_last_position(&all.keys[start]).unwrap();
+            largest_group = largest_group.max(end - start);
+            start = end;
+        }
+        self.largest_group.store(largest_group, Ordering::SeqCst);
+
+        let per_shard = ((all.len() + guards.len() - 1) / guards.len()).max(1);
+
+        let mut splits = Vec::with_capacity(guards.len() - 1);
+        let mut at = per_shard;
+        while splits.len() < guards.len() - 1 && at < all.len() {
+            // never divide the values of a key between shards
+            let prev = splits.last().cloned().unwrap_or(0);
+            let split = all.find_first_position(&all.keys[at]).unwrap();
+            let split = if split <= prev {
+                all.find_last_position(&all.keys[at]).unwrap()
+            } else {
+                split
+            };
+
+            if split >= all.len() {
+                break;
+            }
+
+            splits.push(split);
+            at = split + per_shard;
+        }
+
+        boundaries.clear();
+        boundaries.extend(splits.iter().map(|&split| all.keys[split].clone()));
+
+        for (shard, split) in guards.iter_mut().skip(1).zip(splits.iter()).rev() {
+            **shard = all.split_at_index(*split);
+        }
+        *guards[0] = all;
+    }
+
+    fn read_boundaries(&self) -> RwLockReadGuard<'_, Vec<K>> {
+        read(&self.boundaries)
+    }
+}
+
+fn shard_of<K: Ord>(boundaries: &[K], key: &K) -> usize {
+    boundaries.partition_point(|boundary| boundary <= key)
+}
+
+// the locks are never held over user code which could panic, so poisoned locks still hold a valid
+// list
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<K: Ord, V: PartialEq> fmt::Debug for ShardedSortedList<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "ShardedSortedList {{ shards: {}, len: {} }}",
+            self.shards.len(),
+            self.len.load(Ordering::SeqCst)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedSortedList;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn single_shard() {
+        let list = ShardedSortedList::new(1);
+        assert!(list.insert(1u32, 1u8));
+        assert!(list.insert(0, 0));
+        assert!(!list.insert(1, 1));
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![(0, 0), (1, 1)]);
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn rebalances_skewed_inserts() {
+        let list = ShardedSortedList::new(4);
+        for key in 0..1000u32 {
+            list.insert(key, key);
+        }
+
+        let lens = list.shard_lens();
+        assert_eq!(lens.iter().sum::<usize>(), 1000);
+        assert!(lens.iter().all(|&len| len > 100), "{:?}", lens);
+
+        assert_eq!(
+            list.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            (0..1000).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            list.range(240..=260).map(|(k, _)| k).collect::<Vec<_>>(),
+            (240..=260).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rebalances_skewed_inserts_with_two_shards() {
+        let list = ShardedSortedList::new(2);
+        for key in 0..100_000u32 {
+            list.insert(key, ());
+        }
+
+        let lens = list.shard_lens();
+        assert_eq!(lens.iter().sum::<usize>(), 100_000);
+        assert!(lens.iter().all(|&len| len > 25_000), "{:?}", lens);
+        assert_eq!(list.iter().map(|(k, _)| k).max(), Some(99_999));
+    }
+
+    #[test]
+    fn rebalance_keeps_groups_together() {
+        let list = ShardedSortedList::new(3);
+        for value in 0..100u32 {
+            list.insert(0u32, value);
+            list.insert(1u32, value);
+        }
+        list.insert(2, 0);
+        list.rebalance();
+
+        let lens = list.shard_lens();
+        assert_eq!(lens, vec![100, 100, 1]);
+        assert_eq!(list.values_of(&1), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rebalance_empty_and_single_group() {
+        let list: ShardedSortedList<u32, u32> = ShardedSortedList::new(3);
+        list.rebalance();
+        assert!(list.is_empty());
+
+        for value in 0..10 {
+            list.insert(5, value);
+        }
+        list.rebalance();
+
+        assert_eq!(list.shard_lens(), vec![10, 0, 0]);
+        assert_eq!(list.values_of(&5).len(), 10);
+    }
+
+    #[test]
+    fn large_group_does_not_keep_rebalancing() {
+        let list: ShardedSortedList<u32, u32> = ShardedSortedList::new(4);
+        for value in 0..1000 {
+            list.insert(5, value);
+        }
+        for key in 0..100 {
+            list.insert(key * 10, key);
+        }
+        list.rebalance();
+
+        let lens = list.shard_lens();
+        let largest = *lens.iter().max().unwrap();
+        assert!(largest >= 1000, "{:?}", lens);
+        assert!(!list.needs_rebalance(largest + 1, list.len() + 1));
+        assert!(list.needs_rebalance(2 * largest + 100, list.len() + 1));
+
+        for value in 1000..1500 {
+            list.insert(5, value);
+        }
+        assert_eq!(list.values_of(&5), (0..1500).collect::<Vec<_>>());
+        assert_eq!(list.len(), 1600);
+    }
+
+    #[test]
+    fn parallel_writers() {
+        let list = Arc::new(ShardedSortedList::new(8));
+
+        let handles = (0..8u32)
+            .map(|t| {
+                let list = Arc::clone(&list);
+                thread::spawn(move || {
+                    for i in 0..500u32 {
+                        assert!(list.insert(i * 8 + t, t));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(list.len(), 4000);
+        let keys = list.iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, (0..4000).collect::<Vec<_>>());
+        assert_eq!(list.values_of(&13), vec![5]);
+    }
+}
@@ -0,0 +1,336 @@
+//! All-or-nothing changes to a `SortedList`.
+
+use std::fmt;
+use std::ops::Deref;
+
+use super::SortedList;
+
+/// Guard recording every change made through it to a `SortedList`, created with
+/// `SortedList::begin`.
+///
+/// Unless `commit` is called, the changes are undone when the guard is dropped, including when
+/// unwinding from a panic. Reading the list through the guard sees the changes made so far.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::SortedList;
+///
+/// let mut list = SortedList::new();
+/// list.insert(1u32, 'a');
+///
+/// {
+///     let mut tx = list.begin();
+///     tx.insert(2, 'b');
+///     tx.remove(&1, &'a');
+///     assert_eq!(tx.len(), 1);
+///     // dropped without commit
+/// }
+///
+/// assert_eq!(list.iter().collect::<Vec<_>>(), vec![(&1, &'a')]);
+/// ```
+pub struct Transaction<'a, K: Ord + 'a, V: PartialEq + 'a> {
+    list: &'a mut SortedList<K, V>,
+    /// every change with its number, counting all of the changes ever made through the guard
+    undo: Vec<(usize, Undo<K, V>)>,
+    changes: usize,
+}
+
+/// Position in the changes of a `Transaction` which can be rolled back to, created with
+/// `Transaction::savepoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    len: usize,
+    /// number of the last change before the savepoint, which is replaced once it is rolled back
+    last: Option<usize>,
+}
+
+enum Undo<K, V> {
+    Inserted(usize),
+    Removed(usize, K, V),
+}
+
+impl<'a, K: Ord, V: PartialEq> Transaction<'a, K, V> {
+    pub(crate) fn new(list: &'a mut SortedList<K, V>) -> Self {
+        Transaction {
+            list,
+            undo: Vec::new(),
+            changes: 0,
+        }
+    }
+
+    /// Inserts the tuple, see `SortedList::insert`
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        match self.list.insert_index(key, value) {
+            Some(index) => {
+                self.record(Undo::Inserted(index));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the tuple, see `SortedList::remove`
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        match self.list.remove_index(key, value) {
+            Some((index, key, value)) => {
+                self.record(Undo::Removed(index, key, value));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts the tuple at `index` if it fits there and is not in the list yet. Patches recreate
+    /// the order of another list, so values may go anywhere in their group unless kept sorted.
+    pub(crate) fn insert_at(&mut self, index: usize, key: K, value: V) -> bool {
+        if index > self.list.len()
+            || !self.list.sorted_before(index, &key, &value)
+            || self.list.values_of(&key).contains(&value)
+        {
+            return false;
+        }
+
+        self.list.insert_at(index, key, value);
+        self.record(Undo::Inserted(index));
+        true
+    }
+
+    /// Removes the tuple at `index` if it is `(key, value)`
+    pub(crate) fn remove_at(&mut self, index: usize, key: &K, value: &V) -> bool {
+        if self.list.tuple_at(index) != Some((key, value)) {
+            return false;
+        }
+
+        let (key, value) = self.list.remove_at(index);
+        self.record(Undo::Removed(index, key, value));
+        true
+    }
+
+    fn record(&mut self, undo: Undo<K, V>) {
+        self.undo.push((self.changes, undo));
+        self.changes += 1;
+    }
+
+    /// Returns a savepoint for the changes made so far
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint {
+            len: self.undo.len(),
+            last: self.undo.last().map(|&(change, _)| change),
+        }
+    }
+
+    /// Undoes the changes made after `savepoint` was created. The savepoints created after it can
+    /// no longer be used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if changes made before `savepoint` were already rolled back.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        let last = savepoint
+            .len
+            .checked_sub(1)
+            .and_then(|i| self.undo.get(i))
+            .map(|&(change, _)| change);
+        assert!(
+            savepoint.len <= self.undo.len() && last == savepoint.last,
+            "savepoint has already been rolled back"
+        );
+
+        while self.undo.len() > savepoint.len {
+            match self.undo.pop().unwrap().1 {
+                Undo::Inserted(index) => {
+                    self.list.remove_at(index);
+                }
+                Undo::Removed(index, key, value) => {
+                    self.list.insert_at(index, key, value);
+                }
+            }
+        }
+    }
+
+    /// Runs `f` as a nested transaction, undoing only its changes if it returns `Err`. A panic
+    /// unwinds through the outer transaction, undoing everything.
+    pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Self) -> Result<T, E>,
+    {
+        let savepoint = self.savepoint();
+        let ret = f(self);
+        if ret.is_err() {
+            self.rollback_to(savepoint);
+        }
+        ret
+    }
+
+    /// Keeps the changes
+    pub fn commit(mut self) {
+        self.undo.clear();
+    }
+
+    /// Undoes all of the changes, same as dropping the transaction
+    pub fn rollback(self) {}
+}
+
+impl<'a, K: Ord, V: PartialEq> Deref for Transaction<'a, K, V> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        self.list
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq> Drop for Transaction<'a, K, V> {
+    fn drop(&mut self) {
+        self.rollback_to(Savepoint { len: 0, last: None });
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq> fmt::Debug for Transaction<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Transaction {{ changes: {} }}", self.undo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SortedList;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn sample() -> SortedList<u32, u8> {
+        vec![(0, 0), (0, 1), (1, 2), (3, 3)].into_iter().collect()
+    }
+
+    fn to_vec(list: &SortedList<u32, u8>) -> Vec<(u32, u8)> {
+        list.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    #[test]
+    fn commit_keeps_changes() {
+        let mut list = sample();
+        let res: Result<(), ()> = list.transaction(|tx| {
+            assert!(tx.insert(2, 4));
+            assert!(tx.remove(&0, &0));
+            assert!(!tx.remove(&0, &0));
+            Ok(())
+        });
+
+        assert!(res.is_ok());
+        assert_eq!(to_vec(&list), vec![(0, 1), (1, 2), (2, 4), (3, 3)]);
+    }
+
+    #[test]
+    fn err_rolls_back() {
+        let mut list = sample();
+        let before = to_vec(&list);
+
+        let res: Result<(), _> = list.transaction(|tx| {
+            tx.insert(5, 5);
+            tx.remove(&0, &1);
+            tx.insert(0, 9);
+            tx.remove(&0, &0);
+            tx.insert(0, 1);
+            Err("nope")
+        });
+
+        assert_eq!(res, Err("nope"));
+        assert_eq!(to_vec(&list), before);
+    }
+
+    #[test]
+    fn panic_rolls_back() {
+        let mut list = sample();
+        let before = to_vec(&list);
+
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            let mut tx = list.begin();
+            tx.insert(2, 2);
+            tx.remove(&3, &3);
+            panic!("halfway");
+        }));
+
+        assert!(res.is_err());
+        assert_eq!(to_vec(&list), before);
+    }
+
+    #[test]
+    fn savepoints() {
+        let mut list = sample();
+        {
+            let mut tx = list.begin();
+            tx.insert(4, 4);
+            let first = tx.savepoint();
+            tx.insert(5, 5);
+            let second = tx.savepoint();
+            tx.remove(&0, &0);
+
+            tx.rollback_to(second);
+            assert_eq!(tx.values_of(&0), &[0, 1]);
+            assert_eq!(tx.values_of(&5), &[5]);
+
+            tx.rollback_to(first);
+            assert_eq!(tx.values_of(&5).len(), 0);
+            assert_eq!(tx.values_of(&4), &[4]);
+
+            tx.commit();
+        }
+
+        assert_eq!(to_vec(&list), vec![(0, 0), (0, 1), (1, 2), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn nested_transactions() {
+        let mut list = sample();
+        let res: Result<(), ()> = list.transaction(|tx| {
+            tx.insert(4, 4);
+
+            let inner: Result<(), ()> = tx.transaction(|tx| {
+                tx.insert(5, 5);
+                tx.remove(&4, &4);
+                Err(())
+            });
+            assert!(inner.is_err());
+
+            tx.transaction(|tx| {
+                tx.insert(6, 6);
+                Ok(())
+            })
+        });
+
+        assert!(res.is_ok());
+        assert_eq!(
+            to_vec(&list),
+            vec![(0, 0), (0, 1), (1, 2), (3, 3), (4, 4), (6, 6)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "already been rolled back")]
+    fn stale_savepoint() {
+        let mut list = sample();
+        let mut tx = list.begin();
+        tx.insert(4, 4);
+        let stale = tx.savepoint();
+        tx.rollback_to(super::Savepoint { len: 0, last: None });
+        tx.rollback_to(stale);
+    }
+
+    #[test]
+    #[should_panic(expected = "already been rolled back")]
+    fn savepoint_rolled_back_and_overwritten() {
+        let mut list = SortedList::new();
+        let mut tx = list.begin();
+        let first = tx.savepoint();
+        tx.insert(1u32, 1u8);
+        tx.insert(2, 2);
+        let second = tx.savepoint();
+
+        tx.rollback_to(first);
+        tx.insert(3, 3);
+        tx.insert(4, 4);
+        tx.insert(5, 5);
+
+        // would undo the insert of 5 otherwise
+        tx.rollback_to(second);
+    }
+}
@@ -0,0 +1,441 @@
+//! `SortedList` with entries which expire after a while.
+
+use std::fmt;
+use std::mem;
+use std::ops::RangeBounds;
+use std::time::{Duration, Instant};
+
+use super::{SortedList, Tuples};
+
+/// Source of the current time for a `TtlSortedList`.
+///
+/// Implemented for all `Fn() -> Instant` closures, so that tests can control the time.
+pub trait Clock {
+    /// Returns the current time
+    fn now(&self) -> Instant;
+}
+
+/// `Clock` reading the system time with `Instant::now`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// Value together with the time it expires at. Only the value is compared so that a tuple is
+/// never stored twice with different expiry times.
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    /// identifies the tuple among the expiries
+    id: usize,
+}
+
+/// Key of a tuple in the list of expiries, which holds one for every stored tuple
+#[derive(PartialEq)]
+struct Expiry<K> {
+    key: K,
+    id: usize,
+}
+
+impl<V: PartialEq> PartialEq for Entry<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Entry<V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?}", self.value)
+    }
+}
+
+/// `SortedList` where every tuple expires at a given `Instant`.
+///
+/// Expired tuples are hidden from all of the reading methods right away but are only removed by
+/// `purge_expired`. `insert` calls it too once at least half of the stored tuples have expired, so
+/// that the cleanup stays amortized. A secondary list with the key of every tuple ordered by
+/// expiry time is kept, so that expired tuples are counted quickly and purging only visits the
+/// keys with expired tuples.
+///
+/// # Example
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use std::time::{Duration, Instant};
+/// use sorted_list::TtlSortedList;
+///
+/// let now = Rc::new(Cell::new(Instant::now()));
+/// let clock = {
+///     let now = Rc::clone(&now);
+///     move || now.get()
+/// };
+///
+/// let mut list = TtlSortedList::with_clock(clock);
+/// list.insert_for(1u32, 'a', Duration::from_secs(10));
+/// list.insert_for(1, 'b', Duration::from_secs(60));
+///
+/// now.set(now.get() + Duration::from_secs(30));
+/// assert_eq!(list.values_of(&1), vec![&'b']);
+/// ```
+pub struct TtlSortedList<K: Ord, V: PartialEq, C = SystemClock> {
+    list: SortedList<K, Entry<V>>,
+    expiries: SortedList<Instant, Expiry<K>>,
+    next_id: usize,
+    clock: C,
+}
+
+impl<K: Ord + Clone, V: PartialEq> TtlSortedList<K, V> {
+    /// Creates a new empty list using the system time
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq, C: Clock> TtlSortedList<K, V, C> {
+    /// Creates a new empty list reading the time from `clock`
+    pub fn with_clock(clock: C) -> Self {
+        TtlSortedList {
+            list: SortedList::new(),
+            expiries: SortedList::new(),
+            next_id: 0,
+            clock,
+        }
+    }
+
+    /// Inserts the tuple to expire at `expires_at`. If the tuple already exists its expiry time is
+    /// replaced and `false` returned.
+    pub fn insert(&mut self, key: K, value: V, expires_at: Instant) -> bool {
+        let now = self.clock.now();
+        if 2 * self.expiries.partition_point(|at| *at <= now) >= self.list.len() {
+            self.purge_expired(now);
+        }
+
+        match self.position_of(&key, &value) {
+            Some(index) => {
+                let entry = &mut self.list.values[index];
+                let old = mem::replace(&mut entry.expires_at, expires_at);
+                let expiry = Expiry { key, id: entry.id };
+                self.expiries.remove(&old, &expiry);
+                self.expiries.insert(expires_at, expiry);
+                false
+            }
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.expiries.insert(
+                    expires_at,
+                    Expiry {
+                        key: key.clone(),
+                        id,
+                    },
+                );
+                self.list.insert(
+                    key,
+                    Entry {
+                        value,
+                        expires_at,
+                        id,
+                    },
+                )
+            }
+        }
+    }
+
+    /// Inserts the tuple to expire after `ttl` from now, see `insert`
+    pub fn insert_for(&mut self, key: K, value: V, ttl: Duration) -> bool {
+        let expires_at = self.clock.now() + ttl;
+        self.insert(key, value, expires_at)
+    }
+
+    /// Removes the tuple, returning `true` if it existed and had not expired yet
+    pub fn remove(&mut self, key: &K, value: &V) -> bool {
+        let now = self.clock.now();
+        match self.position_of(key, value) {
+            Some(index) => {
+                let (key, entry) = self.list.remove_at(index);
+                let expiry = Expiry { key, id: entry.id };
+                self.expiries.remove(&entry.expires_at, &expiry);
+                entry.expires_at > now
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the time the tuple expires at, or `None` if it does not exist or has expired
+    pub fn expires_at(&self, key: &K, value: &V) -> Option<Instant> {
+        let now = self.clock.now();
+        self.position_of(key, value)
+            .map(|index| self.list.values[index].expires_at)
+            .filter(|expires_at| *expires_at > now)
+    }
+
+    /// Returns the values of a key which have not expired, in insertion order
+    pub fn values_of(&self, key: &K) -> Vec<&V> {
+        let now = self.clock.now();
+        self.list
+            .values_of(key)
+            .iter()
+            .filter(|entry| entry.expires_at > now)
+            .map(|entry| &entry.value)
+            .collect()
+    }
+
+    /// Returns an iterator over the tuples which have not expired
+    pub fn iter(&self) -> TtlTuples<'_, K, V> {
+        TtlTuples {
+            tuples: self.list.iter(),
+            now: self.clock.now(),
+        }
+    }
+
+    /// Returns an iterator over the specified range of tuples which have not expired
+    pub fn range<R>(&self, range: R) -> TtlTuples<'_, K, V>
+    where
+        R: RangeBounds<K>,
+    {
+        TtlTuples {
+            tuples: self.list.range(range),
+            now: self.clock.now(),
+        }
+    }
+
+    /// Returns the number of stored tuples, including the expired ones not purged yet
+    pub fn stored_len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Removes all of the tuples which expired at or before `now`, returning how many were
+    /// removed. Only the keys which had a tuple expiring by `now` are visited, and the remaining
+    /// tuples are moved once.
+    pub fn purge_expired(&mut self, now: Instant) -> usize {
+        let mut keys = self
+            .expiries
+            .drain(..=now)
+            .map(|(_, expiry)| expiry.key)
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+
+        // other values of the keys might not have expired yet
+        let mut expired = Vec::new();
+        for key in &keys {
+            let (first, last) = match self.list.find_first_position(key) {
+                Ok(first) => (first, self.list.find_last_position(key).unwrap()),
+                Err(_) => continue,
+            };
+
+            let values = &self.list.values;
+            expired.extend((first..last).filter(|&index| values[index].expires_at <= now));
+        }
+
+        self.list.remove_positions(&expired);
+        expired.len()
+    }
+
+    fn position_of(&self, key: &K, value: &V) -> Option<usize> {
+        let first = self.list.find_first_position(key).ok()?;
+        self.list
+            .values_of(key)
+            .iter()
+            .position(|entry| entry.value == *value)
+            .map(|offset| first + offset)
+    }
+}
+
+impl<K: Ord + Clone, V: PartialEq> Default for TtlSortedList<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug, C> fmt::Debug for TtlSortedList<K, V, C> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "TtlSortedList {{ {:?} }}", self.list.iter())
+    }
+}
+
+/// Iterator over the tuples of a `TtlSortedList` which had not expired when it was created
+pub struct TtlTuples<'a, K: 'a, V: 'a> {
+    tuples: Tuples<'a, K, Entry<V>>,
+    now: Instant,
+}
+
+impl<'a, K, V> Iterator for TtlTuples<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = self.now;
+        self.tuples
+            .find(|(_, entry)| entry.expires_at > now)
+            .map(|(key, entry)| (key, &entry.value))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for TtlTuples<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let now = self.now;
+        self.tuples
+            .rfind(|(_, entry)| entry.expires_at > now)
+            .map(|(key, entry)| (key, &entry.value))
+    }
+}
+
+impl<'a, K: Ord + fmt::Debug, V: PartialEq + fmt::Debug> fmt::Debug for TtlTuples<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "TtlTuples {{ {:?} }}", self.tuples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TtlSortedList;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    fn manual_clock() -> (Rc<Cell<Instant>>, impl Fn() -> Instant) {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let clock = {
+            let now = Rc::clone(&now);
+            move || now.get()
+        };
+        (now, clock)
+    }
+
+    fn advance(now: &Cell<Instant>, secs: u64) {
+        now.set(now.get() + Duration::from_secs(secs));
+    }
+
+    #[test]
+    fn expired_tuples_are_hidden() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        list.insert_for(1u32, 'a', Duration::from_secs(10));
+        list.insert_for(2, 'b', Duration::from_secs(20));
+        list.insert_for(2, 'c', Duration::from_secs(5));
+
+        advance(&now, 7);
+        assert_eq!(list.values_of(&2), vec![&'b']);
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            vec![(&1, &'a'), (&2, &'b')]
+        );
+        assert_eq!(list.range(2..).rev().collect::<Vec<_>>(), vec![(&2, &'b')]);
+        assert_eq!(list.expires_at(&2, &'c'), None);
+        assert_eq!(list.stored_len(), 3);
+
+        advance(&now, 7);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![(&2, &'b')]);
+        assert!(!list.remove(&1, &'a'));
+    }
+
+    #[test]
+    fn purge_only_removes_expired() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        for i in 0..10u32 {
+            list.insert_for(i % 3, i, Duration::from_secs(u64::from(i) + 1));
+        }
+
+        advance(&now, 5);
+        assert_eq!(list.purge_expired(now.get()), 5);
+        assert_eq!(list.stored_len(), 5);
+        assert_eq!(list.purge_expired(now.get()), 0);
+
+        assert_eq!(
+            list.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(0, 6), (0, 9), (1, 7), (2, 5), (2, 8)]
+        );
+    }
+
+    #[test]
+    fn reinsert_refreshes_expiry() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        assert!(list.insert_for(1u32, 'a', Duration::from_secs(10)));
+        advance(&now, 8);
+        assert!(!list.insert_for(1, 'a', Duration::from_secs(10)));
+        assert_eq!(list.stored_len(), 1);
+
+        // the first expiry time passing must not remove the refreshed tuple
+        advance(&now, 5);
+        assert_eq!(list.purge_expired(now.get()), 0);
+        assert_eq!(list.values_of(&1), vec![&'a']);
+
+        advance(&now, 5);
+        assert_eq!(list.purge_expired(now.get()), 1);
+        assert_eq!(list.stored_len(), 0);
+    }
+
+    #[test]
+    fn insert_purges() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        for i in 0..100u32 {
+            list.insert_for(i, i, Duration::from_secs(1));
+            advance(&now, 1);
+        }
+
+        assert_eq!(list.stored_len(), 1);
+    }
+
+    #[test]
+    fn purge_large_group_and_drop_removed_expiries() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        for i in 0..2000u32 {
+            list.insert_for(i % 2, i, Duration::from_secs(u64::from(i % 4) + 1));
+        }
+        assert_eq!(list.expiries.len(), 2000);
+
+        assert!(list.remove(&0, &2));
+        assert!(!list.insert_for(0, 4, Duration::from_secs(4)));
+        assert_eq!(list.expiries.len(), 1999);
+        for i in (1..2000u32).step_by(4) {
+            assert!(list.remove(&1, &i));
+        }
+        assert_eq!(list.expiries.len(), 1499);
+
+        advance(&now, 3);
+        assert_eq!(list.purge_expired(now.get()), 1000 - 2);
+        assert_eq!(list.expiries.len(), 501);
+        assert_eq!(list.stored_len(), 501);
+        assert!(list.iter().all(|(_, v)| v % 4 == 3 || *v == 4));
+    }
+
+    #[test]
+    fn insert_purges_values_sharing_an_expiry() {
+        let (now, clock) = manual_clock();
+        let mut list = TtlSortedList::with_clock(clock);
+
+        let expires_at = now.get() + Duration::from_secs(1);
+        for i in 0..1000u32 {
+            list.insert(0u32, i, expires_at);
+        }
+        list.insert_for(1, 0, Duration::from_secs(10));
+
+        advance(&now, 2);
+        list.insert_for(2, 0, Duration::from_secs(10));
+        assert_eq!(list.stored_len(), 2);
+        assert_eq!(list.expiries.len(), 2);
+    }
+}
@@ -0,0 +1,396 @@
+//! `SortedList` keeping only the most recent window of timestamped tuples.
+
+use std::fmt;
+use std::ops::{Add, Deref, Sub};
+
+use super::{SortedList, Tuples};
+
+/// `SortedList` keyed by timestamps which evicts every tuple older than `newest_key - window`
+/// when a newer tuple is inserted.
+///
+/// The keys can be anything which a window span `W` can be added to and which subtract into one,
+/// such as `Instant` with `Duration` or plain integers. Keys are only ever subtracted from newer
+/// ones, so windows reaching past the largest key do not overflow. Eviction removes from the front
+/// of the list, so it takes time proportional to the number of evicted tuples. All of the
+/// read-only methods of `SortedList` are available through `Deref`.
+///
+/// # Example
+///
+/// ```
+/// use sorted_list::WindowedSortedList;
+///
+/// let mut list = WindowedSortedList::new(10u64);
+/// list.insert(1, "a");
+/// list.insert(5, "b");
+/// list.insert(12, "c");
+///
+/// assert_eq!(list.keys().collect::<Vec<_>>(), vec![&5, &12]);
+///
+/// let windows = list
+///     .tumbling_windows(5)
+///     .map(|(start, tuples)| (start, tuples.count()))
+///     .collect::<Vec<_>>();
+/// assert_eq!(windows, vec![(5, 1), (10, 1)]);
+/// ```
+pub struct WindowedSortedList<K: Ord, V: PartialEq, W> {
+    list: SortedList<K, V>,
+    window: W,
+}
+
+impl<K, V, W> WindowedSortedList<K, V, W>
+where
+    K: Ord + Clone + Add<W, Output = K> + Sub<Output = W>,
+    V: PartialEq,
+    W: Copy + Ord + Add<Output = W> + Sub<Output = W>,
+{
+    /// Creates a new empty list keeping the tuples within `window` of the newest key
+    pub fn new(window: W) -> Self {
+        WindowedSortedList {
+            list: SortedList::new(),
+            window,
+        }
+    }
+
+    /// Returns the span of the window
+    pub fn window(&self) -> W {
+        self.window
+    }
+
+    /// Inserts the tuple, evicting the tuples which fall out of the window if `key` is the newest
+    /// one. Returns `false` if the tuple already exists or is too old to fit in the window.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if let Some(newest) = self.list.keys.back() {
+            if key < *newest && newest.clone() - key.clone() > self.window {
+                return false;
+            }
+        }
+
+        if !self.list.insert(key, value) {
+            return false;
+        }
+
+        let newest = self.list.keys.back().unwrap().clone();
+        let window = self.window;
+        let expired = self
+            .list
+            .partition_point(|k| newest.clone() - k.clone() > window);
+
+        for _ in 0..expired {
+            self.list.pop_first();
+        }
+
+        true
+    }
+
+    /// Returns an iterator over consecutive non-overlapping windows of `size`, starting from the
+    /// oldest key. See `sliding_windows`.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if adding `size` to a key does not move it forward.
+    pub fn tumbling_windows(&self, size: W) -> Windows<'_, K, V, W> {
+        self.sliding_windows(size, size)
+    }
+
+    /// Returns an iterator over windows of `size` with starts `step` apart, beginning from the
+    /// oldest key. Each item is the start of the window with the tuples in
+    /// `start..start + size`. Windows without any tuples are skipped over in a logarithmic number
+    /// of steps.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if adding `step` to a key does not move it forward.
+    pub fn sliding_windows(&self, size: W, step: W) -> Windows<'_, K, V, W> {
+        Windows {
+            keys: self.list.key_slice(),
+            values: self.list.value_slice(),
+            start: self.list.keys.front().cloned(),
+            low: 0,
+            size,
+            step,
+        }
+    }
+
+    /// Returns the tuples in the window as a `SortedList`
+    pub fn into_inner(self) -> SortedList<K, V> {
+        self.list
+    }
+}
+
+impl<K: Ord, V: PartialEq, W> Deref for WindowedSortedList<K, V, W> {
+    type Target = SortedList<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: PartialEq + fmt::Debug, W: fmt::Debug> fmt::Debug
+    for WindowedSortedList<K, V, W>
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "WindowedSortedList {{ window: {:?}, list: {:?} }}",
+            self.window, self.list
+        )
+    }
+}
+
+/// Iterator over windows of a `WindowedSortedList`, created by `tumbling_windows` and
+/// `sliding_windows`
+pub struct Windows<'a, K: 'a, V: 'a, W> {
+    keys: &'a [K],
+    values: &'a [V],
+    /// start of the next window, `None` when done
+    start: Option<K>,
+    /// index of the first key not before `start`
+    low: usize,
+    size: W,
+    step: W,
+}
+
+impl<'a, K, V, W> Iterator for Windows<'a, K, V, W>
+where
+    K: Ord + Clone + Add<W, Output = K> + Sub<Output = W>,
+    W: Copy + Ord + Add<Output = W> + Sub<Output = W>,
+{
+    type Item = (K, Tuples<'a, K, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.start.take()?;
+
+        // the starts only move forward, so the earlier keys never need to be searched again
+        let low = self.low + self.keys[self.low..].partition_point(|k| *k < start);
+        let last = self.keys.last()?;
+        if low == self.keys.len() {
+            return None;
+        }
+
+        let start = self.skip_to(start, &self.keys[low], last)?;
+        let size = self.size;
+        let high = low + self.keys[low..].partition_point(|k| k.clone() - start.clone() < size);
+
+        self.start = self.next_start(&start, last);
+        self.low = low;
+
+        let tuples = Tuples {
+            keys: self.keys,
+            values: self.values,
+            low,
+            high,
+        };
+        Some((start, tuples))
+    }
+}
+
+impl<'a, K, V, W> Windows<'a, K, V, W>
+where
+    K: Ord + Clone + Add<W, Output = K> + Sub<Output = W>,
+    W: Copy + Ord + Add<Output = W> + Sub<Output = W>,
+{
+    /// Returns the first start from `start` onwards whose window contains `key`, which is not
+    /// before `start`, or `None` if the starts pass `last` before that. Skips the empty windows
+    /// by doubling the jump, so that sparse keys do not take a step per window.
+    fn skip_to(&self, mut start: K, key: &K, last: &K) -> Option<K> {
+        loop {
+            let gap = key.clone() - start.clone();
+            if gap < self.size {
+                return Some(start);
+            }
+
+            let next = self.next_start(&start, last)?;
+            let remaining = gap - self.size;
+            let mut jump = self.step;
+            // every window skipped over ends at or before `key`
+            while jump <= remaining && jump <= remaining - jump {
+                jump = jump + jump;
+            }
+
+            start = if jump <= remaining {
+                start + jump
+            } else {
+                next
+            };
+        }
+    }
+
+    /// Returns the start after `start`, or `None` if it would pass `last`
+    fn next_start(&self, start: &K, last: &K) -> Option<K> {
+        if self.step > last.clone() - start.clone() {
+            return None;
+        }
+
+        let next = start.clone() + self.step;
+        assert!(next > *start, "window step must move the start forward");
+        Some(next)
+    }
+}
+
+impl<'a, K: fmt::Debug, V, W: fmt::Debug> fmt::Debug for Windows<'a, K, V, W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "Windows {{ start: {:?}, size: {:?}, step: {:?} }}",
+            self.start, self.size, self.step
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WindowedSortedList;
+    use std::time::{Duration, Instant};
+
+    fn windows(
+        list: &WindowedSortedList<u64, u64, u64>,
+        size: u64,
+        step: u64,
+    ) -> Vec<(u64, Vec<u64>)> {
+        list.sliding_windows(size, step)
+            .map(|(start, tuples)| (start, tuples.map(|(_, v)| *v).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn evicts_old_tuples() {
+        let mut list = WindowedSortedList::new(10u64);
+
+        for t in 0..30u64 {
+            assert!(list.insert(t, t));
+        }
+        assert_eq!(list.first(), Some((&19, &19)));
+        assert_eq!(list.len(), 11);
+
+        // older than the window
+        assert!(!list.insert(18, 0));
+        // inside the window but not the newest, nothing is evicted
+        assert!(list.insert(20, 1));
+        assert!(!list.insert(20, 1));
+        assert_eq!(list.len(), 12);
+
+        assert!(list.insert(100, 0));
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![(&100, &0)]);
+    }
+
+    #[test]
+    fn tumbling() {
+        let mut list = WindowedSortedList::new(100u64);
+        for &t in &[3, 4, 7, 8, 8, 20, 21] {
+            list.insert(t, t);
+        }
+
+        assert_eq!(
+            list.tumbling_windows(5)
+                .map(|(start, tuples)| (start, tuples.map(|(_, v)| *v).collect::<Vec<_>>()))
+                .collect::<Vec<_>>(),
+            vec![(3, vec![3, 4, 7]), (8, vec![8]), (18, vec![20, 21])]
+        );
+    }
+
+    #[test]
+    fn sliding() {
+        let mut list = WindowedSortedList::new(100u64);
+        for t in 0..6u64 {
+            list.insert(t * 2, t);
+        }
+
+        assert_eq!(
+            windows(&list, 4, 2),
+            vec![
+                (0, vec![0, 1]),
+                (2, vec![1, 2]),
+                (4, vec![2, 3]),
+                (6, vec![3, 4]),
+                (8, vec![4, 5]),
+                (10, vec![5]),
+            ]
+        );
+
+        let empty: WindowedSortedList<u64, u64, u64> = WindowedSortedList::new(1);
+        assert_eq!(windows(&empty, 4, 2), vec![]);
+    }
+
+    #[test]
+    fn instants() {
+        let origin = Instant::now();
+        let mut list = WindowedSortedList::new(Duration::from_secs(60));
+
+        for s in 0..120u64 {
+            list.insert(origin + Duration::from_secs(s), s);
+        }
+
+        assert_eq!(list.len(), 61);
+        assert_eq!(
+            list.tumbling_windows(Duration::from_secs(30))
+                .map(|(_, tuples)| tuples.count())
+                .collect::<Vec<_>>(),
+            vec![30, 30, 1]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "step must move")]
+    fn zero_step() {
+        let mut list = WindowedSortedList::new(10u64);
+        list.insert(1, 1);
+        list.sliding_windows(1, 0).count();
+    }
+
+    #[test]
+    fn keys_near_the_maximum() {
+        let mut list = WindowedSortedList::new(u64::MAX);
+        assert!(list.insert(0, 0));
+        assert!(list.insert(u64::MAX, 1));
+        assert!(list.insert(1, 2));
+        assert_eq!(list.len(), 3);
+
+        let mut list = WindowedSortedList::new(10u64);
+        for t in u64::MAX - 20..=u64::MAX {
+            list.insert(t, t - (u64::MAX - 20));
+        }
+        assert!(!list.insert(u64::MAX - 11, 0));
+        assert_eq!(list.len(), 11);
+
+        assert_eq!(
+            windows(&list, 4, 4),
+            vec![
+                (u64::MAX - 10, vec![10, 11, 12, 13]),
+                (u64::MAX - 6, vec![14, 15, 16, 17]),
+                (u64::MAX - 2, vec![18, 19, 20]),
+            ]
+        );
+        assert_eq!(windows(&list, 100, 100).len(), 1);
+    }
+
+    #[test]
+    fn sparse_keys_skip_empty_windows() {
+        let mut list = WindowedSortedList::new(u64::MAX);
+        for &t in &[5, 1_000_000_000_000, 1_000_000_000_003, u64::MAX - 1] {
+            list.insert(t, t % 7);
+        }
+
+        assert_eq!(
+            windows(&list, 2, 1)
+                .into_iter()
+                .map(|(start, _)| start)
+                .collect::<Vec<_>>(),
+            vec![
+                5,
+                999_999_999_999,
+                1_000_000_000_000,
+                1_000_000_000_002,
+                1_000_000_000_003,
+                u64::MAX - 2,
+                u64::MAX - 1,
+            ]
+        );
+        assert_eq!(
+            windows(&list, 3, 3)
+                .into_iter()
+                .map(|(start, _)| start)
+                .collect::<Vec<_>>(),
+            vec![5, 999_999_999_998, 1_000_000_000_001, u64::MAX - 1]
+        );
+    }
+}